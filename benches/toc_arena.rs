@@ -0,0 +1,107 @@
+//! Compares the arena representation of a TOC with the tree of nodes, for
+//! reading the TOC, rebuilding the paths of the files and looking up paths.
+
+// `criterion_group!` generates an undocumented public function
+#![allow(missing_docs)]
+
+use std::path::Path;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::TocArena;
+use tempfile::TempDir;
+
+const DIRECTORY_COUNT: usize = 16;
+const FILES_PER_DIRECTORY: usize = 4096;
+
+/// Writes a cache pair with a few large directories, like `/Lotus/Sounds`,
+/// and returns its reader along with the paths of its files.
+fn build_cache_pair(dir: &TempDir) -> (CachePairReader, Vec<String>) {
+    let toc_path = dir.path().join("H.Bench.toc");
+    let cache_path = dir.path().join("H.Bench.cache");
+
+    let mut paths = Vec::with_capacity(DIRECTORY_COUNT * FILES_PER_DIRECTORY);
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    for dir_index in 0..DIRECTORY_COUNT {
+        for file_index in 0..FILES_PER_DIRECTORY {
+            let path = format!("/Lotus/Sounds/Dir{dir_index}/File{file_index}.wav");
+            writer.add_file(path.as_str(), &[], Codec::Stored).unwrap();
+            paths.push(path);
+        }
+    }
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, Some(true));
+    reader.read_toc().unwrap();
+    (reader, paths)
+}
+
+fn toc_arena(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    let (mut reader, paths) = build_cache_pair(&dir);
+    let toc_path = reader.toc_path();
+    let arena = TocArena::read(&toc_path).unwrap();
+
+    // Criterion has no memory measurement, report the estimates once
+    println!(
+        "toc_arena/memory: tree ~{} bytes, arena {} bytes",
+        reader.toc_memory_usage(),
+        arena.memory_usage()
+    );
+
+    let mut group = c.benchmark_group("toc_arena/read");
+    group.sample_size(20);
+    group.bench_function("tree", |b| {
+        b.iter(|| {
+            reader.unread_toc();
+            reader.read_toc().unwrap();
+        })
+    });
+    group.bench_function("arena", |b| {
+        b.iter(|| black_box(TocArena::read(&toc_path).unwrap()))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("toc_arena/paths");
+    group.bench_function("tree", |b| {
+        b.iter(|| {
+            for file in reader.files() {
+                black_box(file.path());
+            }
+        })
+    });
+    group.bench_function("arena", |b| {
+        let mut path = String::new();
+        b.iter(|| {
+            for file in arena.files() {
+                file.write_path(&mut path);
+                black_box(&path);
+            }
+        })
+    });
+    group.finish();
+
+    // Spread the lookups over the whole range of children
+    let samples: Vec<&Path> = paths.iter().step_by(97).map(Path::new).collect();
+
+    let mut group = c.benchmark_group("toc_arena/lookup");
+    group.bench_function("tree", |b| {
+        b.iter(|| {
+            for path in &samples {
+                black_box(reader.get_file_node(*path));
+            }
+        })
+    });
+    group.bench_function("arena", |b| {
+        b.iter(|| {
+            for path in &samples {
+                black_box(arena.get_node(path));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, toc_arena);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+//! Compares resolving paths through the TOC path index with walking the tree
+//! one component at a time.
+
+// `criterion_group!` generates an undocumented public function
+#![allow(missing_docs)]
+
+use std::path::{Component, Path};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::{DirectoryNode, Node};
+use tempfile::TempDir;
+
+const DIRECTORY_COUNT: usize = 16;
+const FILES_PER_DIRECTORY: usize = 4096;
+
+/// Writes a cache pair with a few large directories, like `/Lotus/Sounds`.
+fn build_cache_pair(dir: &TempDir) -> (CachePairReader, Vec<String>) {
+    let toc_path = dir.path().join("H.Bench.toc");
+    let cache_path = dir.path().join("H.Bench.cache");
+
+    let mut paths = Vec::with_capacity(DIRECTORY_COUNT * FILES_PER_DIRECTORY);
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    for dir_index in 0..DIRECTORY_COUNT {
+        for file_index in 0..FILES_PER_DIRECTORY {
+            let path = format!("/Lotus/Sounds/Dir{dir_index}/File{file_index}.wav");
+            writer.add_file(path.as_str(), &[], Codec::Stored).unwrap();
+            paths.push(path);
+        }
+    }
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, Some(true));
+    reader.read_toc().unwrap();
+    (reader, paths)
+}
+
+/// Resolves the path by scanning the children of each directory on the way.
+fn child_scan(root: &Node, path: &Path) -> Option<Node> {
+    let mut current_node = root.clone();
+    for component in path.components().skip(1) {
+        match component {
+            Component::Normal(name) => current_node = current_node.get_child(name.to_str()?)?,
+            _ => return None,
+        }
+    }
+    Some(current_node)
+}
+
+fn toc_lookup(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    let (reader, paths) = build_cache_pair(&dir);
+    let root = reader.get_directory_node("/").unwrap();
+
+    // Spread the lookups over the whole range of children
+    let samples: Vec<&Path> = paths.iter().step_by(97).map(Path::new).collect();
+
+    let mut group = c.benchmark_group("toc_lookup");
+    group.bench_function("path_index", |b| {
+        b.iter(|| {
+            for path in &samples {
+                black_box(reader.get_file_node(*path));
+            }
+        })
+    });
+    group.bench_function("child_scan", |b| {
+        b.iter(|| {
+            for path in &samples {
+                black_box(child_scan(&root, path));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, toc_lookup);
+criterion_main!(benches);
@@ -1,11 +1,7 @@
-use std::fs::File;
-use std::io::{Seek, SeekFrom};
-
 use anyhow::{Error, Result};
 use bytebuffer::ByteBuffer;
 use log::debug;
-use lotus_lib::cache_pair::{CachePair, CachePairReader};
-use lotus_lib::compression::{decompress_post_ensmallening, get_block_lengths};
+use lotus_lib::cache_pair::CachePairReader;
 use lotus_lib::package::{Package, PackageType};
 use lotus_lib::toc::{FileNode, Node};
 
@@ -88,34 +84,21 @@ impl Texture for Package<CachePairReader> {
             debug!("Real image size: {}", header.size() as u64);
             debug!("Decompressed image size: {}", file_node.len() as u64);
 
-            let f_cache_offsets = &header.f_cache_image_offsets;
-
-            if f_cache_offsets.len() != 0 {
-                let cache_image_sub_offset = f_cache_offsets.last().unwrap_or(&0).to_owned();
-
-                let mut f_cache_reader = File::open(f_cache.cache_path()).unwrap();
-                let real_cache_image_sub_offset = get_real_cache_image_offset(
-                    &mut f_cache_reader,
-                    file_node.cache_offset() as usize,
-                    cache_image_sub_offset as usize,
-                )?;
-
-                debug!("Cache image offset: {}", cache_image_sub_offset);
-                debug!("Real cache image offset: {}", real_cache_image_sub_offset);
-
-                f_cache_reader.seek(SeekFrom::Current(real_cache_image_sub_offset as i64))?;
-
-                let file_data = decompress_post_ensmallening(
-                    file_node.comp_len() as usize,
-                    header.size() as usize,
-                    &mut f_cache_reader,
-                )?;
-                buffer.write_bytes(&file_data);
-            } else {
-                // Fall back to the old method if the cache image offsets are not present
-                let file_data = f_cache.decompress_data(file_node)?;
-                buffer.write_bytes(&file_data[file_data.len() - header.size()..]);
-            }
+            // The image is the last mip, at the last cache image offset if there is one,
+            // otherwise at the end of the file data
+            let file_len = file_node.len() as u64;
+            let image_offset = match header.f_cache_image_offsets.last() {
+                Some(&offset) => {
+                    debug!("Cache image offset: {}", offset);
+                    offset as u64
+                }
+                None => file_len.saturating_sub(header.size() as u64),
+            };
+            let file_data = f_cache.decompress_range(
+                &file_node,
+                image_offset..image_offset + header.size() as u64,
+            )?;
+            buffer.write_bytes(&file_data);
         } else {
             let b_cache = match b_cache {
                 Some(b_cache) => b_cache,
@@ -128,51 +111,18 @@ impl Texture for Package<CachePairReader> {
             debug!("Real image size: {}", header.size() as u64);
             debug!("Decompressed image size: {}", file_node.len() as u64);
 
-            let file_data = b_cache.decompress_data(file_node)?;
-            buffer.write_bytes(&file_data[file_data.len() - header.size()..]);
+            let file_len = file_node.len() as u64;
+            let file_data = b_cache.decompress_range(
+                &file_node,
+                file_len.saturating_sub(header.size() as u64)..file_len,
+            )?;
+            buffer.write_bytes(&file_data);
         }
 
         Ok((buffer.into_vec(), get_texture_file_name(node)))
     }
 }
 
-fn get_real_cache_image_offset(
-    cache_reader: &mut File,
-    cache_image_offset: usize,
-    cache_image_sub_offset: usize,
-) -> Result<usize> {
-    cache_reader.seek(SeekFrom::Start(cache_image_offset as u64))?;
-
-    const BLOCK_HEADER_LEN: usize = 8;
-
-    let mut cache_offset_top: usize = 0;
-    let mut cache_offset_bottom: usize = 0;
-
-    loop {
-        let (block_compressed_len, _) = get_block_lengths(cache_reader)?.unwrap_or((0, 0));
-        cache_offset_top += block_compressed_len as usize + BLOCK_HEADER_LEN;
-
-        if cache_offset_top >= cache_image_sub_offset {
-            break;
-        }
-
-        cache_offset_bottom = cache_offset_top;
-        cache_reader.seek(SeekFrom::Current(block_compressed_len as i64))?;
-    }
-
-    // Seek back to the start of the block
-    cache_reader.seek(SeekFrom::Start(cache_image_offset as u64))?;
-
-    let diff_top = cache_offset_top - cache_image_sub_offset;
-    let diff_bottom = cache_image_sub_offset - cache_offset_bottom;
-
-    if diff_top > diff_bottom {
-        return Ok(cache_offset_bottom);
-    } else {
-        return Ok(cache_offset_top);
-    }
-}
-
 fn get_texture_file_name(node: &Node) -> String {
     let mut file_name = node.name();
     if file_name.ends_with(".png") {
@@ -1,21 +1,29 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
+use crate::toc::{ARCHIVE_VERSION, MAGIC_NUMBER};
+use crate::Result;
 
 /// Cache pair trait.
 pub trait CachePair {
     /// The magic number for the cache pair.
-    const MAGIC_NUMBER: u64 = 0x1867C64E;
+    const MAGIC_NUMBER: u64 = MAGIC_NUMBER as u64;
     /// The archive version for the cache pair.
-    const ARCHIVE_VERSION: u64 = 20;
+    const ARCHIVE_VERSION: u64 = ARCHIVE_VERSION as u64;
 
     /// Creates a new cache pair from the specified TOC and cache paths.
-    fn new(toc_path: PathBuf, cache_path: PathBuf, is_post_ensmallening: bool) -> Self;
+    ///
+    /// The compression format is detected when the TOC is read, by sampling the compressed
+    /// entries of the cache file. `is_post_ensmallening` overrides the detection if it is set.
+    fn new(toc_path: PathBuf, cache_path: PathBuf, is_post_ensmallening: Option<bool>) -> Self;
 
     /// Returns whether the package is post-ensmallening.
     ///
     /// This is used to determine how to decompress the data from before "The Great Ensmallening"
     /// update of Warframe.
+    ///
+    /// Unless overridden in [`Self::new`], this is only known once the TOC has been read, and
+    /// defaults to `true` until then or if the format cannot be detected, such as when no entry is
+    /// compressed.
     fn is_post_ensmallening(&self) -> bool;
 
     /// Returns the Table of Contents (TOC) file path.
@@ -28,7 +36,8 @@ pub trait CachePair {
     ///
     /// # Errors
     ///
-    /// Returns an error if the TOC file cannot be read.
+    /// Returns an error if the TOC file cannot be read, if it does not start with
+    /// [`Self::MAGIC_NUMBER`] or if its version is not [`Self::ARCHIVE_VERSION`].
     fn read_toc(&mut self) -> Result<()>;
 
     /// Unreads the TOC file.
@@ -1,30 +1,53 @@
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+#[cfg(feature = "mmap")]
+use filebuffer::FileBuffer;
 
 use crate::cache_pair::cache_pair::CachePair;
+use crate::cache_pair::compact::{
+    compact_entries, compact_path, is_same_file, replace_pair, CompactReport,
+};
+use crate::cache_pair::entry_reader::{entry_lengths, CacheSource, EntryReader};
+use crate::cache_pair::extract::{extract_files, ExtractOptions, ExtractReport};
+use crate::cache_pair::find::{FindOptions, Matcher};
+use crate::cache_pair::hash::{hash_entry, ContentHash, HashAlgorithm};
+use crate::cache_pair::space::{space_report, SpaceReport};
+use crate::cache_pair::verify::{verify_entries, VerifyReport};
+use crate::compression::block::BlockInfo;
+use crate::compression::detect::detect_post_ensmallening;
 use crate::compression::post_ensmallening::decompress_post_ensmallening;
 use crate::compression::pre_ensmallening::decompress_pre_ensmallening;
-use crate::toc::{FileNode, Node, Toc};
+use crate::toc::{
+    read_toc_entries, read_whole_toc_entries, FileNode, FileVersion, Node, Toc, TocEntry,
+};
+use crate::{Error, Result};
 
 /// A cache pair reader.
 pub struct CachePairReader {
+    is_post_ensmallening_override: Option<bool>,
     is_post_ensmallening: bool,
     toc_path: PathBuf,
     cache_path: PathBuf,
     toc: Toc,
+    #[cfg(feature = "mmap")]
+    cache_map: Option<FileBuffer>,
 }
 
 impl CachePair for CachePairReader {
-    fn new(toc_path: PathBuf, cache_path: PathBuf, is_post_ensmallening: bool) -> Self {
+    fn new(toc_path: PathBuf, cache_path: PathBuf, is_post_ensmallening: Option<bool>) -> Self {
         let toc = Toc::new(toc_path.clone());
         Self {
-            is_post_ensmallening,
+            is_post_ensmallening_override: is_post_ensmallening,
+            is_post_ensmallening: is_post_ensmallening.unwrap_or(true),
             toc_path,
             cache_path,
             toc,
+            #[cfg(feature = "mmap")]
+            cache_map: None,
         }
     }
 
@@ -41,15 +64,67 @@ impl CachePair for CachePairReader {
     }
 
     fn read_toc(&mut self) -> Result<()> {
-        self.toc.read_toc()
+        if self.toc.is_loaded() {
+            return Ok(()); // TOC already loaded
+        }
+
+        self.toc.read_toc()?;
+        self.resolve_post_ensmallening();
+
+        Ok(())
     }
 
     fn unread_toc(&mut self) {
-        self.toc.unread_toc()
+        self.toc.unread_toc();
+        self.is_post_ensmallening = self.is_post_ensmallening_override.unwrap_or(true);
     }
 }
 
 impl CachePairReader {
+    /// Get the archive version parsed from the TOC header.
+    ///
+    /// Returns `None` if the TOC has not been read.
+    pub fn version(&self) -> Option<u32> {
+        self.toc.version()
+    }
+
+    /// Returns whether the TOC has been read.
+    pub fn is_toc_loaded(&self) -> bool {
+        self.toc.is_loaded()
+    }
+
+    /// Read the TOC from the given index file if it is still valid, or from the TOC file otherwise.
+    ///
+    /// The index file holds the parsed entries of the TOC file, without the replaced ones unless
+    /// the history is kept, and is keyed by the size and modification time of the TOC file. It is
+    /// stale as soon as the TOC file changes, or if it lacks the replaced versions while the
+    /// history is kept, see [`Self::set_keep_history`]. A stale, missing or corrupt index file is
+    /// rewritten after reading the TOC file; failing to write it is not an error.
+    ///
+    /// Returns whether the TOC was loaded from the index file, which is `false` if the TOC was
+    /// already loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOC file has to be read and cannot be, see [`CachePair::read_toc`].
+    pub fn read_toc_with_index<P: AsRef<Path>>(&mut self, index_path: P) -> Result<bool> {
+        if self.toc.is_loaded() {
+            return Ok(false); // TOC already loaded
+        }
+
+        let from_index = self.toc.read_toc_with_index(index_path.as_ref())?;
+        self.resolve_post_ensmallening();
+
+        Ok(from_index)
+    }
+
+    /// Returns an estimate of the memory used by the nodes of the TOC, in bytes.
+    ///
+    /// Returns 0 if the TOC has not been read.
+    pub fn toc_memory_usage(&self) -> usize {
+        self.toc.memory_usage()
+    }
+
     /// Get the directory node for the given path.
     pub fn get_directory_node<T: Into<PathBuf>>(&self, path: T) -> Option<Node> {
         self.toc.get_directory_node(path.into())
@@ -60,6 +135,37 @@ impl CachePairReader {
         self.toc.get_file_node(path.into())
     }
 
+    /// Returns whether the replaced versions of the files are kept when reading the TOC.
+    pub fn keeps_history(&self) -> bool {
+        self.toc.keeps_history()
+    }
+
+    /// Set whether the replaced versions of the files are kept when reading the TOC.
+    ///
+    /// Replaced versions are entries with a timestamp of 0, superseded by a newer entry with the
+    /// same path. They are dropped by default. This only takes effect the next time the TOC is
+    /// read, so [`CachePair::unread_toc`] must be called first if it is already loaded.
+    pub fn set_keep_history(&mut self, keep_history: bool) {
+        self.toc.set_keep_history(keep_history)
+    }
+
+    /// Get the replaced versions of the file at the given path, from oldest to newest.
+    ///
+    /// The current version is returned by [`Self::get_file_node`]. The data of a replaced version
+    /// can be read with [`Self::decompress_data`] if it is still present in the cache file.
+    ///
+    /// Returns an empty slice if the history is not kept, see [`Self::set_keep_history`].
+    pub fn replaced_versions<T: Into<PathBuf>>(&self, path: T) -> &[FileVersion] {
+        self.toc.replaced_versions(&path.into())
+    }
+
+    /// Get the replaced versions of all the files.
+    ///
+    /// Returns an empty iterator if the history is not kept, see [`Self::set_keep_history`].
+    pub fn replaced_files(&self) -> impl Iterator<Item = &FileVersion> {
+        self.toc.replaced_files()
+    }
+
     /// Get the directory nodes
     pub fn directories(&self) -> &Vec<Node> {
         self.toc.directories()
@@ -70,41 +176,483 @@ impl CachePairReader {
         self.toc.files()
     }
 
+    /// Find the file nodes whose path matches the given glob pattern.
+    ///
+    /// The pattern is matched against the whole path of the files, such as
+    /// `/Lotus/Characters/Tenno/**/*.png` or `**/*Footstep*.{wav,ogg}`. Paths are absolute, so
+    /// `*.wav` matches nothing, use `**/*.wav` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern is invalid.
+    pub fn find(&self, pattern: &str) -> Result<impl Iterator<Item = Node> + '_> {
+        self.find_with(pattern, &FindOptions::default())
+    }
+
+    /// Find the file nodes whose path matches the given pattern, with the given options.
+    ///
+    /// See [`PatternSyntax`](crate::cache_pair::PatternSyntax) for the supported syntaxes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern is invalid.
+    pub fn find_with(
+        &self,
+        pattern: &str,
+        options: &FindOptions,
+    ) -> Result<impl Iterator<Item = Node> + '_> {
+        let matcher = Matcher::new(pattern, options)?;
+
+        // Only walk the subtree the pattern can match
+        let base_directory = matcher.base_directory().unwrap_or("/");
+        let files = self
+            .get_directory_node(base_directory)
+            .map(|directory| directory.walk().files());
+
+        Ok(files
+            .into_iter()
+            .flatten()
+            .map(|(_, node)| node)
+            .filter(move |node| matcher.is_match(node)))
+    }
+
+    /// Memory-map the cache file.
+    ///
+    /// Once mapped, the cache file is opened only once and every read is served from the mapping
+    /// instead of reopening the file. Stored (uncompressed) entries can then be borrowed directly
+    /// with [`Self::get_data_cow`] and [`Self::decompress_data_cow`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file cannot be opened or mapped.
+    #[cfg(feature = "mmap")]
+    pub fn map_cache(&mut self) -> Result<()> {
+        if self.cache_map.is_none() {
+            self.cache_map = Some(FileBuffer::open(&self.cache_path)?);
+        }
+        Ok(())
+    }
+
+    /// Unmap the cache file.
+    ///
+    /// Subsequent reads reopen the cache file on every call.
+    #[cfg(feature = "mmap")]
+    pub fn unmap_cache(&mut self) {
+        self.cache_map = None;
+    }
+
+    /// Returns whether the cache file is memory-mapped.
+    #[cfg(feature = "mmap")]
+    pub fn is_cache_mapped(&self) -> bool {
+        self.cache_map.is_some()
+    }
+
+    fn mapped_cache(&self) -> Option<&[u8]> {
+        #[cfg(feature = "mmap")]
+        return self.cache_map.as_deref();
+
+        #[cfg(not(feature = "mmap"))]
+        return None;
+    }
+
     /// Read the data without decompressing it for the given file node.
-    pub fn get_data(&self, file_node: Node) -> Result<Vec<u8>> {
-        let mut cache_reader = File::open(self.cache_path.clone()).unwrap();
-        cache_reader
-            .seek(SeekFrom::Start(file_node.cache_offset() as u64))
-            .unwrap();
-
-        let mut data = vec![0; file_node.comp_len() as usize];
-        cache_reader.read_exact(&mut data).unwrap();
+    pub fn get_data<F: FileNode>(&self, file_node: F) -> Result<Vec<u8>> {
+        if let Some(cache) = self.mapped_cache() {
+            return Ok(mapped_entry(cache, &file_node)?.to_vec());
+        }
+
+        let mut cache_reader = File::open(self.cache_path.clone())?;
+        cache_reader.seek(SeekFrom::Start(file_node.cache_offset() as u64))?;
+
+        let (comp_len, _) = entry_lengths(&file_node)?;
+        let mut data = vec![0; comp_len];
+        cache_reader.read_exact(&mut data)?;
         Ok(data)
     }
 
+    /// Read the data without decompressing it for the given file node.
+    ///
+    /// The data is borrowed from the memory-mapped cache file if it is mapped, and read into an
+    /// owned buffer otherwise.
+    pub fn get_data_cow<F: FileNode + ?Sized>(&self, file_node: &F) -> Result<Cow<'_, [u8]>> {
+        if let Some(cache) = self.mapped_cache() {
+            return Ok(Cow::Borrowed(mapped_entry(cache, file_node)?));
+        }
+
+        self.get_data(file_node).map(Cow::Owned)
+    }
+
     /// Read and decompress the data for the given file node.
     ///
     /// If the file is not compressed, the data is read without decompressing it.
-    pub fn decompress_data(&self, file_node: Node) -> Result<Vec<u8>> {
+    pub fn decompress_data<F: FileNode>(&self, file_node: F) -> Result<Vec<u8>> {
         if file_node.comp_len() == file_node.len() {
             return self.get_data(file_node);
         }
 
-        let mut cache_reader = File::open(self.cache_path.clone()).unwrap();
+        if let Some(cache) = self.mapped_cache() {
+            return self.decompress_from(&file_node, &mut Cursor::new(cache));
+        }
+
+        let mut cache_reader = File::open(self.cache_path.clone())?;
+        self.decompress_from(&file_node, &mut cache_reader)
+    }
+
+    /// Read and decompress the data for the given file node.
+    ///
+    /// If the file is not compressed and the cache file is memory-mapped, the data is borrowed
+    /// from the mapping without copying it.
+    pub fn decompress_data_cow<F: FileNode + ?Sized>(
+        &self,
+        file_node: &F,
+    ) -> Result<Cow<'_, [u8]>> {
+        if file_node.comp_len() == file_node.len() {
+            return self.get_data_cow(file_node);
+        }
+
+        self.decompress_data(file_node).map(Cow::Owned)
+    }
+
+    /// Open a streaming reader over the decompressed data for the given file node.
+    ///
+    /// Unlike [`Self::decompress_data`], the data is decompressed one block at a time as it is
+    /// read, so large files do not need to be held in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file cannot be opened or if the block headers of the file
+    /// are corrupt.
+    pub fn open_entry<F: FileNode + ?Sized>(&self, file_node: &F) -> Result<EntryReader<'_>> {
+        let cache_reader = match self.mapped_cache() {
+            Some(cache) => CacheSource::Mapped(Cursor::new(cache)),
+            None => CacheSource::File(File::open(&self.cache_path)?),
+        };
+
+        EntryReader::new(cache_reader, file_node, self.is_post_ensmallening)
+    }
+
+    /// Decompress the given range of the data for the given file node.
+    ///
+    /// Only the blocks overlapping the range are read and decompressed, so the end of a large file
+    /// can be read without decompressing the beginning. The range is in decompressed bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidRange`] if the range is decreasing or goes past the end of the
+    /// file, or an error if the cache file cannot be opened or if the data cannot be decompressed.
+    pub fn decompress_range<F: FileNode + ?Sized>(
+        &self,
+        file_node: &F,
+        range: Range<u64>,
+    ) -> Result<Vec<u8>> {
+        let mut entry = self.open_entry(file_node)?;
+        if range.start > range.end || range.end > entry.len() {
+            return Err(Error::InvalidRange {
+                start: range.start,
+                end: range.end,
+                len: entry.len(),
+            });
+        }
+
+        entry.read_range(range)
+    }
+
+    /// Get the blocks the data of the given file node is split into, in order.
+    ///
+    /// Only the block headers are read, the data is not decompressed. See [`BlockInfo`] for the
+    /// layout of the blocks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file cannot be opened or if the block headers of the file
+    /// are corrupt.
+    pub fn blocks<F: FileNode + ?Sized>(&self, file_node: &F) -> Result<Vec<BlockInfo>> {
+        Ok(self.open_entry(file_node)?.blocks().to_vec())
+    }
+
+    /// Hash the decompressed data of the given file node with the given algorithm.
+    ///
+    /// The data is streamed through the hasher one block at a time, like with
+    /// [`Self::open_entry`], so large files do not need to be held in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data cannot be read or decompressed.
+    pub fn hash_entry<F: FileNode + ?Sized>(
+        &self,
+        file_node: &F,
+        algorithm: HashAlgorithm,
+    ) -> Result<ContentHash> {
+        hash_entry(&mut self.open_entry(file_node)?, algorithm)
+    }
+
+    /// Check the integrity of every entry of the TOC against the cache file.
+    ///
+    /// Every entry is checked, including the directories and the replaced entries, whether the TOC
+    /// has been read or not. The checks are that:
+    ///
+    /// - the name is valid UTF-8 and the parent directory exists,
+    /// - the data lies within the cache file,
+    /// - the block chain covers exactly the compressed and decompressed lengths,
+    /// - the data decompresses.
+    ///
+    /// Checks do not stop at the first failure, every corrupt entry is listed in the returned
+    /// [`VerifyReport`] along with all of its errors. Unless it is overridden, the format of the
+    /// cache file is detected if the TOC has not been read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOC file cannot be read or has an invalid header, or if the cache
+    /// file cannot be opened.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let (_, entries, truncated_len) = read_whole_toc_entries(&self.toc_path)?;
+
+        verify_entries(
+            &entries,
+            truncated_len,
+            &self.cache_path,
+            self.mapped_cache(),
+            self.entries_post_ensmallening(&entries),
+        )
+    }
+
+    /// Write a compacted copy of the cache pair to the given paths.
+    ///
+    /// Only the live entries are kept: the replaced entries, which have a timestamp of 0, are
+    /// dropped, and the data of the files is copied as is, packed in the order of the TOC. Files
+    /// sharing the same data keep sharing it. The new cache pair is then verified, see
+    /// [`Self::verify`], and removed if it is corrupt. The original cache pair is only read.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidPath`] if a path leads to one of the original files,
+    /// [`Error::UnverifiedCompaction`] if the new cache pair fails verification, or an error if
+    /// the original TOC file cannot be read or is invalid, if the data of a file is out of the
+    /// cache file, or if the new cache pair cannot be written.
+    pub fn compact_to<P, Q>(&self, toc_path: P, cache_path: Q) -> Result<CompactReport>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (toc_path, cache_path) = (toc_path.as_ref(), cache_path.as_ref());
+        for path in [toc_path, cache_path] {
+            if is_same_file(path, &self.toc_path) || is_same_file(path, &self.cache_path) {
+                return Err(Error::InvalidPath(path.to_path_buf()));
+            }
+        }
+
+        let (_, entries) = read_toc_entries(&self.toc_path)?;
+        let is_post_ensmallening = self.entries_post_ensmallening(&entries);
+
+        let compacted = match self.mapped_cache() {
+            Some(cache) => compact_entries(
+                &entries,
+                CacheSource::Mapped(Cursor::new(cache)),
+                cache.len() as u64,
+                toc_path,
+                cache_path,
+            ),
+            None => {
+                let cache_file = File::open(&self.cache_path)?;
+                let cache_len = cache_file.metadata()?.len();
+                compact_entries(
+                    &entries,
+                    CacheSource::File(cache_file),
+                    cache_len,
+                    toc_path,
+                    cache_path,
+                )
+            }
+        };
+
+        // Only a verified cache pair is kept
+        let verified = compacted.and_then(|report| {
+            let compacted_pair = CachePairReader::new(
+                toc_path.to_path_buf(),
+                cache_path.to_path_buf(),
+                Some(is_post_ensmallening),
+            );
+            let verify_report = compacted_pair.verify()?;
+            if !verify_report.is_success() {
+                return Err(Error::UnverifiedCompaction(Box::new(verify_report)));
+            }
+            Ok(report)
+        });
+        if verified.is_err() {
+            let _ = fs::remove_file(toc_path);
+            let _ = fs::remove_file(cache_path);
+        }
+
+        verified
+    }
+
+    /// Compact the cache pair in place.
+    ///
+    /// The compacted cache pair is written next to the original one, with a `.compact` extension
+    /// appended to the file names, see [`Self::compact_to`]. The original files are only replaced
+    /// once it verifies, the cache file first. If the TOC file cannot be replaced afterwards, the
+    /// compacted TOC file is left next to it, and renaming it completes the compaction.
+    ///
+    /// The TOC is read again if it was loaded, and the cache file is mapped again if it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compaction fails, see [`Self::compact_to`], if the original files
+    /// cannot be replaced, or if the TOC cannot be read again or the cache file mapped again.
+    pub fn compact(&mut self) -> Result<CompactReport> {
+        let compact_toc_path = compact_path(&self.toc_path);
+        let compact_cache_path = compact_path(&self.cache_path);
+        let report = self.compact_to(&compact_toc_path, &compact_cache_path)?;
+
+        // A mapped file cannot be replaced on Windows
+        #[cfg(feature = "mmap")]
+        let was_mapped = self.cache_map.take().is_some();
+        let was_loaded = self.toc.is_loaded();
+        self.unread_toc();
+
+        let replaced = replace_pair(
+            &compact_toc_path,
+            &compact_cache_path,
+            &self.toc_path,
+            &self.cache_path,
+        );
+
+        #[cfg(feature = "mmap")]
+        if was_mapped {
+            self.map_cache()?;
+        }
+        if was_loaded {
+            self.read_toc()?;
+        }
+
+        replaced.map(|()| report)
+    }
+
+    /// Report how the space of the cache file is used by the entries of the TOC.
+    ///
+    /// The report compares the bytes referenced by the files with the length of the cache file,
+    /// separately for the live files and for their replaced versions, whether the history is kept
+    /// or not. It lists the unreferenced ranges and the overlapping files, and breaks down the
+    /// space used by the live files per directory and per extension.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOC file cannot be read or is invalid, or if the length of the cache
+    /// file cannot be read.
+    pub fn space_report(&self) -> Result<SpaceReport> {
+        let (_, entries) = read_toc_entries(&self.toc_path)?;
+        let cache_len = match self.mapped_cache() {
+            Some(cache) => cache.len() as u64,
+            None => fs::metadata(&self.cache_path)?.len(),
+        };
+
+        space_report(&entries, cache_len)
+    }
+
+    /// Extract the files matching the filter to the given directory.
+    ///
+    /// The files are decompressed in parallel and written to the directory following their path in
+    /// the TOC, creating the intermediate directories as needed. The TOC must have been read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOC has not been read or if the output directory cannot be created.
+    /// Errors related to a single file do not stop the extraction, they are reported in the
+    /// returned [`ExtractReport`].
+    pub fn extract_to<P, F>(
+        &self,
+        directory: P,
+        filter: F,
+        options: &ExtractOptions<'_>,
+    ) -> Result<ExtractReport>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Node) -> bool,
+    {
+        if !self.toc.is_loaded() {
+            return Err(Error::TocNotRead(self.toc_path.clone()));
+        }
+        extract_files(self, directory.as_ref(), filter, options)
+    }
+
+    /// Returns the format of the cache file for the given raw entries: the
+    /// overridden or detected format if the TOC has been read, or the one
+    /// detected from the entries otherwise.
+    fn entries_post_ensmallening(&self, entries: &[TocEntry]) -> bool {
+        match self.is_post_ensmallening_override {
+            Some(is_post_ensmallening) => is_post_ensmallening,
+            None if self.toc.is_loaded() => self.is_post_ensmallening,
+            None => {
+                let files = entries
+                    .iter()
+                    .filter(|entry| entry.timestamp != 0 && !entry.is_directory())
+                    .filter_map(|entry| {
+                        let comp_len = usize::try_from(entry.comp_len).ok()?;
+                        let len = usize::try_from(entry.len).ok()?;
+                        Some((entry.cache_offset as u64, comp_len, len))
+                    });
+                let detected = match self.mapped_cache() {
+                    Some(cache) => detect_post_ensmallening(&mut Cursor::new(cache), files),
+                    None => File::open(&self.cache_path)
+                        .ok()
+                        .and_then(|mut cache_file| {
+                            detect_post_ensmallening(&mut cache_file, files)
+                        }),
+                };
+                detected.unwrap_or(true)
+            }
+        }
+    }
+
+    /// Detects the format of the cache file once the TOC is read, unless it is
+    /// overridden.
+    fn resolve_post_ensmallening(&mut self) {
+        if self.is_post_ensmallening_override.is_none() {
+            self.is_post_ensmallening = self.detect_post_ensmallening().unwrap_or(true);
+        }
+    }
+
+    /// Samples the compressed files to detect the format of the cache file.
+    ///
+    /// Returns `None` if the cache file cannot be opened or if no file is conclusive.
+    fn detect_post_ensmallening(&self) -> Option<bool> {
+        let entries = self.files().iter().filter_map(|file_node| {
+            let (comp_len, len) = entry_lengths(file_node).ok()?;
+            Some((file_node.cache_offset() as u64, comp_len, len))
+        });
+
+        match self.mapped_cache() {
+            Some(cache) => detect_post_ensmallening(&mut Cursor::new(cache), entries),
+            None => detect_post_ensmallening(&mut File::open(&self.cache_path).ok()?, entries),
+        }
+    }
+
+    fn decompress_from<F: FileNode + ?Sized, R: Read + Seek>(
+        &self,
+        file_node: &F,
+        cache_reader: &mut R,
+    ) -> Result<Vec<u8>> {
+        let (comp_len, len) = entry_lengths(file_node)?;
         cache_reader.seek(SeekFrom::Start(file_node.cache_offset() as u64))?;
 
         if self.is_post_ensmallening {
-            return decompress_post_ensmallening(
-                file_node.comp_len() as usize,
-                file_node.len() as usize,
-                &mut cache_reader,
-            );
+            decompress_post_ensmallening(comp_len, len, cache_reader)
         } else {
-            return decompress_pre_ensmallening(
-                file_node.comp_len() as usize,
-                file_node.len() as usize,
-                &mut cache_reader,
-            );
+            decompress_pre_ensmallening(comp_len, len, cache_reader)
         }
     }
 }
+
+/// Returns the raw bytes of the given file node from a memory-mapped cache file.
+fn mapped_entry<'a, F: FileNode + ?Sized>(cache: &'a [u8], file_node: &F) -> Result<&'a [u8]> {
+    let (comp_len, _) = entry_lengths(file_node)?;
+    let start = file_node.cache_offset() as usize;
+    let end = start.saturating_add(comp_len);
+
+    cache.get(start..end).ok_or(Error::EntryOutOfBounds {
+        index: None,
+        cache_offset: file_node.cache_offset(),
+        comp_len: file_node.comp_len(),
+        cache_len: cache.len() as u64,
+    })
+}
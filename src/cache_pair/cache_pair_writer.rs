@@ -0,0 +1,367 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::num::NonZeroI64;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cache_pair::cache_pair::CachePair;
+use crate::compression::detect::detect_post_ensmallening;
+use crate::compression::post_ensmallening::compress_post_ensmallening;
+use crate::compression::pre_ensmallening::compress_pre_ensmallening;
+use crate::compression::Codec;
+use crate::toc::{read_toc_entries, write_toc_entries, TocEntry};
+use crate::{Error, Result};
+
+/// The number of 100-nanosecond intervals between the Windows epoch (1601-01-01) and the Unix
+/// epoch (1970-01-01).
+const WINDOWS_EPOCH_OFFSET: i64 = 116_444_736_000_000_000;
+
+/// A cache pair writer.
+///
+/// File data is appended to the `.cache` file as soon as it is added, and the `.toc` file is
+/// written by [`CachePairWriter::write_toc`].
+///
+/// By default a new cache pair is created. If [`CachePair::read_toc`] is called before adding
+/// anything, the existing entries are kept and new data is appended to the existing `.cache` file
+/// instead. Like the game does, replacing a file keeps its previous entry with a timestamp of 0.
+///
+/// Unless the format is given to [`CachePair::new`], new cache pairs are written in the
+/// post-ensmallening format, and existing ones are appended to in the format of their entries.
+pub struct CachePairWriter {
+    is_post_ensmallening_override: Option<bool>,
+    is_post_ensmallening: bool,
+    toc_path: PathBuf,
+    cache_path: PathBuf,
+    timestamp: NonZeroI64,
+    is_appending: bool,
+    entries: Vec<TocEntry>,
+    directories: HashMap<PathBuf, i32>,
+    next_directory_index: i32,
+    files: HashMap<PathBuf, usize>,
+    cache_writer: Option<File>,
+    cache_len: u64,
+}
+
+impl CachePair for CachePairWriter {
+    fn new(toc_path: PathBuf, cache_path: PathBuf, is_post_ensmallening: Option<bool>) -> Self {
+        Self {
+            is_post_ensmallening_override: is_post_ensmallening,
+            is_post_ensmallening: is_post_ensmallening.unwrap_or(true),
+            toc_path,
+            cache_path,
+            timestamp: now_timestamp(),
+            is_appending: false,
+            entries: Vec::new(),
+            directories: HashMap::from([(PathBuf::from("/"), 0)]),
+            next_directory_index: 1,
+            files: HashMap::new(),
+            cache_writer: None,
+            cache_len: 0,
+        }
+    }
+
+    fn is_post_ensmallening(&self) -> bool {
+        self.is_post_ensmallening
+    }
+
+    fn toc_path(&self) -> PathBuf {
+        self.toc_path.clone()
+    }
+
+    fn cache_path(&self) -> PathBuf {
+        self.cache_path.clone()
+    }
+
+    fn read_toc(&mut self) -> Result<()> {
+        if self.is_appending {
+            return Ok(()); // TOC already loaded
+        }
+
+        // Discard anything added before reading the existing entries
+        self.unread_toc();
+
+        let (_, entries) = read_toc_entries(&self.toc_path)?;
+
+        let mut directory_paths = vec![PathBuf::from("/")];
+        for (index, entry) in entries.iter().enumerate() {
+            // Replaced entries are kept as is but cannot be looked up
+            if entry.timestamp == 0 {
+                continue;
+            }
+
+            let name = entry
+                .name()
+                .map_err(|source| Error::InvalidEntryName { index, source })?;
+            let parent_path = usize::try_from(entry.parent_dir_index)
+                .ok()
+                .and_then(|parent_index| directory_paths.get(parent_index))
+                .ok_or(Error::ParentIndexOutOfRange {
+                    index,
+                    parent_index: entry.parent_dir_index,
+                })?;
+            let path = parent_path.join(name);
+
+            if entry.is_directory() {
+                // Keep the first directory when a path is duplicated, like
+                // readers do
+                self.directories
+                    .entry(path.clone())
+                    .or_insert(directory_paths.len() as i32);
+                directory_paths.push(path);
+            } else {
+                self.files.insert(path, index);
+            }
+        }
+
+        // Keep appending in the format of the existing entries
+        if self.is_post_ensmallening_override.is_none() {
+            let entries = entries
+                .iter()
+                .filter(|entry| entry.timestamp != 0 && !entry.is_directory())
+                .filter_map(|entry| {
+                    let comp_len = usize::try_from(entry.comp_len).ok()?;
+                    let len = usize::try_from(entry.len).ok()?;
+                    Some((entry.cache_offset as u64, comp_len, len))
+                });
+            self.is_post_ensmallening = File::open(&self.cache_path)
+                .ok()
+                .and_then(|mut cache_reader| detect_post_ensmallening(&mut cache_reader, entries))
+                .unwrap_or(true);
+        }
+
+        // Duplicated directories still take an index
+        self.next_directory_index = directory_paths.len() as i32;
+        self.entries = entries;
+        self.is_appending = true;
+
+        Ok(())
+    }
+
+    fn unread_toc(&mut self) {
+        self.is_appending = false;
+        self.entries.clear();
+        self.directories.clear();
+        self.directories.insert(PathBuf::from("/"), 0);
+        self.next_directory_index = 1;
+        self.files.clear();
+        self.cache_writer = None;
+        self.cache_len = 0;
+        self.is_post_ensmallening = self.is_post_ensmallening_override.unwrap_or(true);
+    }
+}
+
+impl CachePairWriter {
+    /// Returns the timestamp given to the added entries.
+    ///
+    /// Defaults to the creation time of the writer, as a Windows file time.
+    pub fn timestamp(&self) -> NonZeroI64 {
+        self.timestamp
+    }
+
+    /// Sets the timestamp given to the entries added from now on.
+    ///
+    /// A timestamp of 0 marks an entry as replaced, so it is not allowed.
+    pub fn set_timestamp(&mut self, timestamp: NonZeroI64) {
+        self.timestamp = timestamp;
+    }
+
+    /// Add a directory, along with its missing parent directories.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is not absolute, if one of its names is longer than 64 bytes
+    /// or if a file already exists at this path.
+    pub fn add_directory<T: Into<PathBuf>>(&mut self, path: T) -> Result<()> {
+        let path = normalize_path(path.into())?;
+        self.ensure_directory(&path)?;
+        Ok(())
+    }
+
+    /// Add a file, along with its missing parent directories.
+    ///
+    /// The data is compressed with the given codec and appended to the `.cache` file right away.
+    /// If a file already exists at this path, it is replaced.
+    ///
+    /// Blocks that would be ambiguous when read back, or that do not fit in a block once
+    /// compressed, are stored or compressed with LZ4 instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is invalid or if a directory already exists at this path,
+    /// if the codec is not supported by the cache pair format, if the compression fails or if
+    /// the `.cache` file cannot be written.
+    pub fn add_file<T: Into<PathBuf>>(&mut self, path: T, data: &[u8], codec: Codec) -> Result<()> {
+        let path = normalize_path(path.into())?;
+
+        let (parent_path, name) = match (path.parent(), path.file_name()) {
+            (Some(parent_path), Some(name)) => (parent_path, name.to_string_lossy()),
+            _ => return Err(Error::InvalidPath(path)),
+        };
+        if self.directories.contains_key(&path) {
+            return Err(Error::InvalidPath(path));
+        }
+
+        // Encode first, so that no directory is added when the data cannot be encoded
+        let cache_data = self.encode(data, codec)?;
+        let len = i32::try_from(data.len()).map_err(|_| file_too_large())?;
+        let comp_len = i32::try_from(cache_data.len()).map_err(|_| file_too_large())?;
+
+        let parent_dir_index = self.ensure_directory(parent_path)?;
+        let cache_offset = self.write_cache(&cache_data)?;
+
+        let entry = TocEntry::new(
+            &name,
+            cache_offset as i64,
+            self.timestamp.get(),
+            comp_len,
+            len,
+            parent_dir_index,
+        );
+
+        if let Some(replaced_index) = self.files.insert(path, self.entries.len()) {
+            self.entries[replaced_index].timestamp = 0;
+        }
+        self.entries.push(entry);
+
+        Ok(())
+    }
+
+    /// Write the `.toc` file and flush the `.cache` file.
+    ///
+    /// This can be called multiple times, the `.toc` file is rewritten each time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `.toc` or `.cache` file cannot be written.
+    pub fn write_toc(&mut self) -> Result<()> {
+        self.cache_writer()?.flush()?;
+        write_toc_entries(&self.toc_path, &self.entries)
+    }
+
+    fn ensure_directory(&mut self, path: &Path) -> Result<i32> {
+        if let Some(&index) = self.directories.get(path) {
+            return Ok(index);
+        }
+        if self.files.contains_key(path) {
+            return Err(Error::InvalidPath(path.to_path_buf()));
+        }
+
+        // The root directory is always known, so the path has a parent and a name
+        let (parent_path, name) = match (path.parent(), path.file_name()) {
+            (Some(parent_path), Some(name)) => (parent_path, name.to_string_lossy()),
+            _ => return Err(Error::InvalidPath(path.to_path_buf())),
+        };
+        let parent_dir_index = self.ensure_directory(parent_path)?;
+
+        let index = self.next_directory_index;
+        self.next_directory_index += 1;
+        self.entries.push(TocEntry::new(
+            &name,
+            -1,
+            self.timestamp.get(),
+            0,
+            0,
+            parent_dir_index,
+        ));
+        self.directories.insert(path.to_path_buf(), index);
+
+        Ok(index)
+    }
+
+    fn encode<'a>(&self, data: &'a [u8], codec: Codec) -> Result<Cow<'a, [u8]>> {
+        let encoded_data = match codec {
+            Codec::Stored => return Ok(Cow::Borrowed(data)),
+            _ if self.is_post_ensmallening => compress_post_ensmallening(codec, data)?,
+            Codec::Lz4 => compress_pre_ensmallening(data),
+            Codec::Oodle => return Err(Error::UnsupportedCodec(codec)),
+        };
+
+        // Entries with equal compressed and decompressed lengths are read as
+        // is, so they must be stored as is
+        if encoded_data.len() == data.len() {
+            return Ok(Cow::Borrowed(data));
+        }
+
+        Ok(Cow::Owned(encoded_data))
+    }
+
+    fn cache_writer(&mut self) -> Result<&mut File> {
+        let cache_writer = match self.cache_writer.take() {
+            Some(cache_writer) => cache_writer,
+            None => {
+                let cache_writer = if self.is_appending {
+                    OpenOptions::new().append(true).open(&self.cache_path)?
+                } else {
+                    File::create(&self.cache_path)?
+                };
+                self.cache_len = cache_writer.metadata()?.len();
+                cache_writer
+            }
+        };
+
+        Ok(self.cache_writer.insert(cache_writer))
+    }
+
+    /// Appends the data to the `.cache` file and returns its offset.
+    ///
+    /// If the data cannot be written, the `.cache` file is truncated back to its previous length so
+    /// that the next files are written at the offset of their entries.
+    fn write_cache(&mut self, data: &[u8]) -> Result<u64> {
+        self.cache_writer()?;
+        let cache_offset = self.cache_len;
+        let cache_writer = self.cache_writer()?;
+
+        if let Err(error) = cache_writer.write_all(data) {
+            cache_writer.set_len(cache_offset)?;
+            cache_writer.seek(SeekFrom::Start(cache_offset))?;
+            return Err(error.into());
+        }
+
+        self.cache_len += data.len() as u64;
+        Ok(cache_offset)
+    }
+}
+
+/// Checks that the path is absolute and that every name fits in a TOC entry,
+/// and removes any `.` component.
+fn normalize_path(path: PathBuf) -> Result<PathBuf> {
+    let mut components = path.components();
+    if components.next() != Some(Component::RootDir) {
+        return Err(Error::InvalidPath(path));
+    }
+
+    let mut normalized_path = PathBuf::from("/");
+    for component in components {
+        match component {
+            Component::Normal(name) if name.to_str().is_some_and(is_valid_name) => {
+                normalized_path.push(name)
+            }
+            Component::CurDir => continue,
+            _ => return Err(Error::InvalidPath(path)),
+        }
+    }
+
+    Ok(normalized_path)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && !name.contains('\0')
+}
+
+fn file_too_large() -> Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "file is too large for a cache pair",
+    )
+    .into()
+}
+
+fn now_timestamp() -> NonZeroI64 {
+    let since_unix_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let timestamp = WINDOWS_EPOCH_OFFSET + (since_unix_epoch.as_nanos() / 100) as i64;
+    NonZeroI64::new(timestamp).unwrap_or(NonZeroI64::MIN)
+}
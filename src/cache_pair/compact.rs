@@ -0,0 +1,147 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::cache_pair::entry_reader::CacheSource;
+use crate::toc::{write_toc_entries, TocEntry};
+use crate::{Error, Result};
+
+/// The outcome of compacting a cache pair.
+#[derive(Debug, Default)]
+pub struct CompactReport {
+    /// The number of files written to the compacted cache pair.
+    pub files: usize,
+
+    /// The number of directories written to the compacted cache pair.
+    pub directories: usize,
+
+    /// The number of replaced entries dropped, which have a timestamp of 0.
+    pub dropped: usize,
+
+    /// The length of the original cache file.
+    pub old_cache_len: u64,
+
+    /// The length of the compacted cache file.
+    pub new_cache_len: u64,
+}
+
+impl CompactReport {
+    /// Returns the number of bytes saved by the compaction.
+    pub fn reclaimed_len(&self) -> u64 {
+        self.old_cache_len.saturating_sub(self.new_cache_len)
+    }
+}
+
+/// Writes the live entries to a new cache pair, with their data packed in the
+/// order of the TOC.
+pub(super) fn compact_entries(
+    entries: &[TocEntry],
+    mut cache_reader: CacheSource<'_>,
+    cache_len: u64,
+    toc_path: &Path,
+    cache_path: &Path,
+) -> Result<CompactReport> {
+    let mut report = CompactReport {
+        old_cache_len: cache_len,
+        ..Default::default()
+    };
+
+    let mut cache_writer = BufWriter::new(File::create(cache_path)?);
+    let mut new_entries = Vec::with_capacity(entries.len());
+    // Files sharing the same data keep sharing it
+    let mut new_offsets = HashMap::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        // Dropping the replaced directories keeps the parent directory
+        // indices, which only count the live ones
+        if entry.timestamp == 0 {
+            report.dropped += 1;
+            continue;
+        }
+
+        let mut entry = entry.clone();
+        if entry.is_directory() {
+            report.directories += 1;
+            new_entries.push(entry);
+            continue;
+        }
+
+        let (cache_offset, comp_len) = u64::try_from(entry.cache_offset)
+            .ok()
+            .zip(u64::try_from(entry.comp_len).ok())
+            .filter(|(cache_offset, comp_len)| cache_offset + comp_len <= cache_len)
+            .ok_or(Error::EntryOutOfBounds {
+                index: Some(index),
+                cache_offset: entry.cache_offset,
+                comp_len: entry.comp_len,
+                cache_len,
+            })?;
+
+        let new_offset = match new_offsets.entry((cache_offset, comp_len)) {
+            Entry::Occupied(new_offset) => *new_offset.get(),
+            Entry::Vacant(new_offset) => {
+                cache_reader.seek(SeekFrom::Start(cache_offset))?;
+                let copied_len =
+                    io::copy(&mut (&mut cache_reader).take(comp_len), &mut cache_writer)?;
+                if copied_len != comp_len {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+
+                let offset = report.new_cache_len;
+                report.new_cache_len += comp_len;
+                *new_offset.insert(offset)
+            }
+        };
+
+        entry.cache_offset = new_offset as i64;
+        report.files += 1;
+        new_entries.push(entry);
+    }
+
+    cache_writer
+        .into_inner()
+        .map_err(|error| error.into_error())?
+        .sync_all()?;
+    write_toc_entries(toc_path, &new_entries)?;
+    OpenOptions::new().write(true).open(toc_path)?.sync_all()?;
+
+    Ok(report)
+}
+
+/// Returns the path of the compacted version of a file of a cache pair.
+pub(super) fn compact_path(path: &Path) -> PathBuf {
+    let mut compact_path = OsString::from(path.as_os_str());
+    compact_path.push(".compact");
+    PathBuf::from(compact_path)
+}
+
+/// Replaces the original cache pair with the compacted one.
+///
+/// The cache file is replaced first. If the TOC file cannot be replaced afterwards, the compacted
+/// TOC file is left in place, and renaming it completes the compaction.
+pub(super) fn replace_pair(
+    compact_toc_path: &Path,
+    compact_cache_path: &Path,
+    toc_path: &Path,
+    cache_path: &Path,
+) -> Result<()> {
+    if let Err(error) = fs::rename(compact_cache_path, cache_path) {
+        let _ = fs::remove_file(compact_toc_path);
+        let _ = fs::remove_file(compact_cache_path);
+        return Err(error.into());
+    }
+
+    fs::rename(compact_toc_path, toc_path)?;
+    Ok(())
+}
+
+/// Returns whether both paths lead to the same existing file.
+pub(super) fn is_same_file(path: &Path, other_path: &Path) -> bool {
+    match (fs::canonicalize(path), fs::canonicalize(other_path)) {
+        (Ok(path), Ok(other_path)) => path == other_path,
+        _ => false,
+    }
+}
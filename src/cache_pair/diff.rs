@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::io::Read;
+use std::path::PathBuf;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::cache_pair::cache_pair_reader::CachePairReader;
+use crate::toc::{DirectoryNode, FileNode, Node, NodeKind};
+use crate::Result;
+
+/// The length of the chunks compared at once when comparing the content of
+/// two files.
+const CHUNK_LEN: usize = 0x10000;
+
+/// Options for comparing two cache pairs.
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// Whether the decompressed data of the files present in both cache pairs is compared, in
+    /// addition to their timestamp and lengths.
+    ///
+    /// Every file present in both cache pairs is decompressed, which is much slower than only
+    /// comparing the TOCs. Moved files must also have the same data when this is set.
+    pub compare_content: bool,
+}
+
+/// The changes between two cache pairs, as returned by [`diff`].
+///
+/// Every list is sorted by path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TocDiff {
+    /// The files only present in the new cache pair.
+    pub added: Vec<DiffFile>,
+
+    /// The files only present in the old cache pair.
+    pub removed: Vec<DiffFile>,
+
+    /// The files present in both cache pairs under different paths.
+    pub moved: Vec<MovedFile>,
+
+    /// The files present in both cache pairs under the same path that have changed.
+    pub modified: Vec<ModifiedFile>,
+
+    /// The number of files present in both cache pairs under the same path that have not changed.
+    pub unchanged: usize,
+}
+
+impl TocDiff {
+    /// Returns whether the cache pairs hold the same files.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+/// A file of a cache pair, as reported in a [`TocDiff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DiffFile {
+    /// The TOC path of the file.
+    pub path: PathBuf,
+
+    /// The timestamp of the file.
+    pub timestamp: i64,
+
+    /// The compressed length of the file.
+    pub comp_len: i32,
+
+    /// The decompressed length of the file.
+    pub len: i32,
+}
+
+impl DiffFile {
+    fn new(node: &Node) -> Self {
+        Self {
+            path: node.path(),
+            timestamp: node.timestamp(),
+            comp_len: node.comp_len(),
+            len: node.len(),
+        }
+    }
+}
+
+/// A file present in both cache pairs under different paths.
+///
+/// A file is considered moved when it has the same name, timestamp and lengths in both cache
+/// pairs, and the same data if [`DiffOptions::compare_content`] is set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MovedFile {
+    /// The file in the old cache pair.
+    pub old: DiffFile,
+
+    /// The file in the new cache pair.
+    pub new: DiffFile,
+}
+
+/// A file present in both cache pairs under the same path that has changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ModifiedFile {
+    /// The file in the old cache pair.
+    pub old: DiffFile,
+
+    /// The file in the new cache pair.
+    pub new: DiffFile,
+
+    /// What has changed, at least one item.
+    pub changes: Vec<FileChange>,
+}
+
+/// What has changed in a [`ModifiedFile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FileChange {
+    /// The timestamp has changed.
+    Timestamp,
+
+    /// The compressed length has changed.
+    CompLen,
+
+    /// The decompressed length has changed.
+    Len,
+
+    /// The decompressed data has changed, only reported if [`DiffOptions::compare_content`] is
+    /// set.
+    Content,
+}
+
+/// Compares the TOCs of two cache pairs, typically two versions of the same package.
+///
+/// Files are compared by timestamp and lengths, see [`diff_with`] to compare their data as well.
+/// Directories are only compared through the files they hold. The TOC of both cache pairs must be
+/// read, an unread TOC is considered empty.
+pub fn diff(old: &CachePairReader, new: &CachePairReader) -> TocDiff {
+    // Only comparing the content reads the cache files
+    let mut differ = Differ::<Infallible> {
+        old,
+        new,
+        same_content: None,
+        diff: TocDiff::default(),
+    };
+    match differ.run() {
+        Ok(()) => differ.diff,
+        Err(never) => match never {},
+    }
+}
+
+/// Compares the TOCs of two cache pairs with the given options.
+///
+/// See [`diff`].
+///
+/// # Errors
+///
+/// Returns an error if the data of a file cannot be read or decompressed while comparing the
+/// content.
+pub fn diff_with(
+    old: &CachePairReader,
+    new: &CachePairReader,
+    options: &DiffOptions,
+) -> Result<TocDiff> {
+    let same_content =
+        |old_file: &Node, new_file: &Node| same_content(old, new, old_file, new_file);
+    let mut differ = Differ {
+        old,
+        new,
+        same_content: options.compare_content.then_some(&same_content as _),
+        diff: TocDiff::default(),
+    };
+    differ.run()?;
+    Ok(differ.diff)
+}
+
+/// Compares the content of two files, if enabled.
+type ContentComparison<'a, E> = &'a dyn Fn(&Node, &Node) -> std::result::Result<bool, E>;
+
+struct Differ<'a, E> {
+    old: &'a CachePairReader,
+    new: &'a CachePairReader,
+    same_content: Option<ContentComparison<'a, E>>,
+    diff: TocDiff,
+}
+
+impl<E> Differ<'_, E> {
+    fn run(&mut self) -> std::result::Result<(), E> {
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+
+        match (
+            self.old.get_directory_node("/"),
+            self.new.get_directory_node("/"),
+        ) {
+            (Some(old_root), Some(new_root)) => {
+                self.diff_directory(&old_root, &new_root, &mut removed, &mut added)?
+            }
+            (old_root, new_root) => {
+                removed.extend(old_root.iter().flat_map(files_under));
+                added.extend(new_root.iter().flat_map(files_under));
+            }
+        }
+
+        self.match_moved(removed, added)?;
+
+        self.diff.added.sort_by(|a, b| a.path.cmp(&b.path));
+        self.diff.removed.sort_by(|a, b| a.path.cmp(&b.path));
+        self.diff.moved.sort_by(|a, b| a.old.path.cmp(&b.old.path));
+        self.diff
+            .modified
+            .sort_by(|a, b| a.old.path.cmp(&b.old.path));
+
+        Ok(())
+    }
+
+    /// Compares the children of two directories with the same path, and
+    /// collects the files only present on either side.
+    fn diff_directory(
+        &mut self,
+        old_directory: &Node,
+        new_directory: &Node,
+        removed: &mut Vec<Node>,
+        added: &mut Vec<Node>,
+    ) -> std::result::Result<(), E> {
+        // Keep the first child when a name is duplicated, as the path index does
+        let mut children: BTreeMap<String, (Option<Node>, Option<Node>)> = BTreeMap::new();
+        for child in old_directory.children() {
+            let name = child.name();
+            children.entry(name).or_default().0.get_or_insert(child);
+        }
+        for child in new_directory.children() {
+            let name = child.name();
+            children.entry(name).or_default().1.get_or_insert(child);
+        }
+
+        for (old_child, new_child) in children.into_values() {
+            match (old_child, new_child) {
+                (Some(old_child), Some(new_child)) => {
+                    match (old_child.kind(), new_child.kind()) {
+                        (NodeKind::Directory, NodeKind::Directory) => {
+                            self.diff_directory(&old_child, &new_child, removed, added)?
+                        }
+                        (NodeKind::File, NodeKind::File) => {
+                            self.diff_file(&old_child, &new_child)?
+                        }
+                        // A file replaced by a directory, or the other way around
+                        _ => {
+                            removed.extend(files_under(&old_child));
+                            added.extend(files_under(&new_child));
+                        }
+                    }
+                }
+                // A child only present on one side
+                (old_child, new_child) => {
+                    removed.extend(old_child.iter().flat_map(files_under));
+                    added.extend(new_child.iter().flat_map(files_under));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn diff_file(&mut self, old_file: &Node, new_file: &Node) -> std::result::Result<(), E> {
+        let mut changes = Vec::new();
+        if old_file.timestamp() != new_file.timestamp() {
+            changes.push(FileChange::Timestamp);
+        }
+        if old_file.comp_len() != new_file.comp_len() {
+            changes.push(FileChange::CompLen);
+        }
+        if old_file.len() != new_file.len() {
+            changes.push(FileChange::Len);
+        }
+        let same_content = match self.same_content {
+            Some(same_content) => same_content(old_file, new_file)?,
+            None => true,
+        };
+        if !same_content {
+            changes.push(FileChange::Content);
+        }
+
+        if changes.is_empty() {
+            self.diff.unchanged += 1;
+        } else {
+            self.diff.modified.push(ModifiedFile {
+                old: DiffFile::new(old_file),
+                new: DiffFile::new(new_file),
+                changes,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Pairs the removed files with the added files they have been moved to.
+    fn match_moved(&mut self, removed: Vec<Node>, added: Vec<Node>) -> std::result::Result<(), E> {
+        let mut candidates: HashMap<_, Vec<Option<Node>>> = HashMap::new();
+        for node in added {
+            candidates
+                .entry(move_key(&node))
+                .or_default()
+                .push(Some(node));
+        }
+
+        let mut removed: Vec<_> = removed
+            .into_iter()
+            .map(|node| (DiffFile::new(&node), node))
+            .collect();
+        removed.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+
+        for (old, old_node) in removed {
+            let mut moved_to = None;
+            if let Some(candidates) = candidates.get_mut(&move_key(&old_node)) {
+                for candidate in candidates.iter_mut() {
+                    let Some(new_node) = candidate else {
+                        continue;
+                    };
+                    let same_content = match self.same_content {
+                        Some(same_content) => same_content(&old_node, new_node)?,
+                        None => true,
+                    };
+                    if same_content {
+                        moved_to = candidate.take();
+                        break;
+                    }
+                }
+            }
+
+            match moved_to {
+                Some(new_node) => self.diff.moved.push(MovedFile {
+                    old,
+                    new: DiffFile::new(&new_node),
+                }),
+                None => self.diff.removed.push(old),
+            }
+        }
+
+        self.diff.added.extend(
+            candidates
+                .into_values()
+                .flatten()
+                .flatten()
+                .map(|node| DiffFile::new(&node)),
+        );
+
+        Ok(())
+    }
+}
+
+/// Returns whether two files have the same decompressed data, streaming both
+/// chunk by chunk.
+fn same_content(
+    old: &CachePairReader,
+    new: &CachePairReader,
+    old_file: &Node,
+    new_file: &Node,
+) -> Result<bool> {
+    // Files of different lengths cannot have the same data
+    if old_file.len() != new_file.len() {
+        return Ok(false);
+    }
+
+    let mut old_reader = old.open_entry(old_file)?;
+    let mut new_reader = new.open_entry(new_file)?;
+    let mut old_chunk = vec![0; CHUNK_LEN];
+    let mut new_chunk = vec![0; CHUNK_LEN];
+
+    let mut remaining = old_reader.len();
+    while remaining > 0 {
+        let chunk_len = remaining.min(CHUNK_LEN as u64) as usize;
+        old_reader.read_exact(&mut old_chunk[..chunk_len])?;
+        new_reader.read_exact(&mut new_chunk[..chunk_len])?;
+        if old_chunk[..chunk_len] != new_chunk[..chunk_len] {
+            return Ok(false);
+        }
+        remaining -= chunk_len as u64;
+    }
+
+    Ok(true)
+}
+
+/// Returns what a file keeps when it is moved.
+fn move_key(node: &Node) -> (String, i64, i32, i32) {
+    (node.name(), node.timestamp(), node.comp_len(), node.len())
+}
+
+/// Returns the file itself, or every file under the directory.
+fn files_under(node: &Node) -> Vec<Node> {
+    match node.kind() {
+        NodeKind::File => vec![node.clone()],
+        NodeKind::Directory => node.walk().files().map(|(_, file)| file).collect(),
+    }
+}
@@ -0,0 +1,245 @@
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::compression::block::{decompress_block, BlockInfo, Codec, MAX_BLOCK_LEN};
+use crate::compression::post_ensmallening::get_blocks;
+use crate::toc::FileNode;
+use crate::{Error, Result};
+
+/// The cache file an [`EntryReader`] reads from.
+pub(super) enum CacheSource<'a> {
+    /// The cache file opened for this entry only.
+    File(File),
+
+    /// The memory-mapped cache file.
+    Mapped(Cursor<&'a [u8]>),
+}
+
+impl Read for CacheSource<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            CacheSource::File(file) => file.read(buf),
+            CacheSource::Mapped(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for CacheSource<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            CacheSource::File(file) => file.seek(pos),
+            CacheSource::Mapped(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// Returns the compressed and decompressed lengths of the given file node.
+pub(super) fn entry_lengths<F: FileNode + ?Sized>(file_node: &F) -> Result<(usize, usize)> {
+    let to_usize =
+        |len: i32| usize::try_from(len).map_err(|_| Error::InvalidEntryLength { index: None, len });
+    Ok((to_usize(file_node.comp_len())?, to_usize(file_node.len())?))
+}
+
+/// Splits an entry stored without compression into blocks of at most
+/// `MAX_BLOCK_LEN` bytes, so that it is read in bounded chunks like the
+/// compressed entries.
+fn stored_blocks(cache_offset: u64, len: usize) -> Vec<BlockInfo> {
+    (0..len)
+        .step_by(MAX_BLOCK_LEN)
+        .map(|start| {
+            let block_len = (len - start).min(MAX_BLOCK_LEN);
+            BlockInfo {
+                offset: cache_offset + start as u64,
+                comp_len: block_len,
+                decomp_len: block_len,
+                codec: Codec::Stored,
+            }
+        })
+        .collect()
+}
+
+/// A streaming reader over the decompressed data of a single cache entry.
+///
+/// Only the block containing the current position is kept in memory. Seeking jumps directly to the
+/// block containing the new position without decompressing the blocks in between.
+///
+/// Created with [`CachePairReader::open_entry`](crate::cache_pair::CachePairReader::open_entry).
+pub struct EntryReader<'a> {
+    cache_reader: CacheSource<'a>,
+    blocks: Vec<BlockInfo>,
+    block_offsets: Vec<u64>,
+    len: u64,
+    pos: u64,
+    current_block: Option<usize>,
+    compressed_buffer: Vec<u8>,
+    decompressed_buffer: Vec<u8>,
+}
+
+impl<'a> EntryReader<'a> {
+    pub(super) fn new<F: FileNode + ?Sized>(
+        mut cache_reader: CacheSource<'a>,
+        file_node: &F,
+        is_post_ensmallening: bool,
+    ) -> Result<Self> {
+        let cache_offset = file_node.cache_offset() as u64;
+        let (comp_len, len) = entry_lengths(file_node)?;
+
+        let blocks = if comp_len == len {
+            stored_blocks(cache_offset, len)
+        } else if is_post_ensmallening {
+            cache_reader.seek(SeekFrom::Start(cache_offset))?;
+            get_blocks(comp_len, len, &mut cache_reader)?
+        } else {
+            vec![BlockInfo {
+                offset: cache_offset,
+                comp_len,
+                decomp_len: len,
+                codec: Codec::Lz4,
+            }]
+        };
+
+        let block_offsets = blocks
+            .iter()
+            .scan(0, |offset, block| {
+                let block_offset = *offset;
+                *offset += block.decomp_len as u64;
+                Some(block_offset)
+            })
+            .collect();
+
+        Ok(Self {
+            cache_reader,
+            blocks,
+            block_offsets,
+            len: len as u64,
+            pos: 0,
+            current_block: None,
+            compressed_buffer: Vec::new(),
+            decompressed_buffer: Vec::new(),
+        })
+    }
+
+    /// Returns the decompressed length of the entry.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether the entry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the blocks of the entry, in order.
+    pub fn blocks(&self) -> &[BlockInfo] {
+        &self.blocks
+    }
+
+    /// Passes the decompressed data of every block to the given function, in
+    /// order, regardless of the current position. Stops at the first error.
+    pub(super) fn for_each_block<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&[u8]) -> Result<()>,
+    {
+        for index in 0..self.blocks.len() {
+            self.load_block(index)?;
+            f(&self.decompressed_buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the decompressed bytes of the range, decompressing only the
+    /// blocks overlapping it. The range must be within the entry.
+    pub(super) fn read_range(&mut self, range: Range<u64>) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity((range.end - range.start) as usize);
+        if range.is_empty() {
+            return Ok(data);
+        }
+
+        let first_index = self
+            .block_offsets
+            .partition_point(|&offset| offset <= range.start)
+            - 1;
+        for index in first_index..self.blocks.len() {
+            let block_offset = self.block_offsets[index];
+            if block_offset >= range.end {
+                break;
+            }
+
+            self.load_block(index)?;
+            let start = range.start.saturating_sub(block_offset) as usize;
+            let end = (range.end - block_offset).min(self.blocks[index].decomp_len as u64) as usize;
+            data.extend_from_slice(&self.decompressed_buffer[start..end]);
+        }
+
+        Ok(data)
+    }
+
+    fn load_block(&mut self, index: usize) -> Result<()> {
+        if self.current_block == Some(index) {
+            return Ok(());
+        }
+
+        let block = self.blocks[index];
+        self.compressed_buffer.resize(block.comp_len, 0);
+        self.decompressed_buffer.resize(block.decomp_len, 0);
+
+        self.cache_reader.seek(SeekFrom::Start(block.offset))?;
+        self.cache_reader.read_exact(&mut self.compressed_buffer)?;
+
+        // Invalidate the current block in case decompression fails midway
+        self.current_block = None;
+        decompress_block(
+            block.codec,
+            &self.compressed_buffer,
+            &mut self.decompressed_buffer,
+        )?;
+        self.current_block = Some(index);
+
+        Ok(())
+    }
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let index = self
+            .block_offsets
+            .partition_point(|&offset| offset <= self.pos)
+            - 1;
+        self.load_block(index)?;
+
+        let block_pos = (self.pos - self.block_offsets[index]) as usize;
+        let available = &self.decompressed_buffer[block_pos..];
+        let read_len = available.len().min(buf.len());
+
+        buf[..read_len].copy_from_slice(&available[..read_len]);
+        self.pos += read_len as u64;
+
+        Ok(read_len)
+    }
+}
+
+impl Seek for EntryReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = new_pos;
+                Ok(new_pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::cache_pair::cache_pair_reader::CachePairReader;
+use crate::toc::Node;
+use crate::{Error, Result};
+
+/// Options for extracting files from a cache pair.
+#[derive(Default)]
+pub struct ExtractOptions<'a> {
+    /// The number of threads decompressing files.
+    ///
+    /// Defaults to the available parallelism of the system.
+    pub threads: Option<NonZeroUsize>,
+
+    /// A callback called from the worker threads every time a file has been processed.
+    pub progress: Option<&'a (dyn Fn(ExtractProgress<'_>) + Sync)>,
+}
+
+/// The progress of an extraction, reported every time a file has been processed.
+#[derive(Debug)]
+pub struct ExtractProgress<'a> {
+    /// The file that has been processed.
+    pub node: &'a Node,
+
+    /// The error that occurred while extracting the file, if any.
+    pub error: Option<&'a Error>,
+
+    /// The number of files processed so far, including this one.
+    pub completed: usize,
+
+    /// The total number of files to extract.
+    pub total: usize,
+}
+
+/// The outcome of an extraction.
+#[derive(Debug, Default)]
+pub struct ExtractReport {
+    /// The TOC paths of the files that have been extracted.
+    pub extracted: Vec<PathBuf>,
+
+    /// The TOC paths of the files that could not be extracted, along with the error.
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+impl ExtractReport {
+    /// Returns whether every file has been extracted.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Appends the outcome of another extraction to this one.
+    pub fn merge(&mut self, other: ExtractReport) {
+        self.extracted.extend(other.extracted);
+        self.failed.extend(other.failed);
+    }
+}
+
+pub(super) fn extract_files<F>(
+    cache_pair: &CachePairReader,
+    directory: &Path,
+    filter: F,
+    options: &ExtractOptions<'_>,
+) -> Result<ExtractReport>
+where
+    F: Fn(&Node) -> bool,
+{
+    fs::create_dir_all(directory)?;
+
+    let nodes: Vec<&Node> = cache_pair
+        .files()
+        .iter()
+        .filter(|node| filter(node))
+        .collect();
+    let total = nodes.len();
+
+    let threads = options
+        .threads
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(total.max(1));
+
+    let next_index = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let report = Mutex::new(ExtractReport::default());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                let Some(node) = nodes.get(index) else {
+                    break;
+                };
+
+                let result = extract_file(cache_pair, directory, node);
+                let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+
+                if let Some(progress) = options.progress {
+                    progress(ExtractProgress {
+                        node,
+                        error: result.as_ref().err(),
+                        completed,
+                        total,
+                    });
+                }
+
+                let mut report = report.lock().unwrap_or_else(|error| error.into_inner());
+                match result {
+                    Ok(()) => report.extracted.push(node.path()),
+                    Err(error) => report.failed.push((node.path(), error)),
+                }
+            });
+        }
+    });
+
+    Ok(report
+        .into_inner()
+        .unwrap_or_else(|error| error.into_inner()))
+}
+
+fn extract_file(cache_pair: &CachePairReader, directory: &Path, node: &Node) -> Result<()> {
+    let output_path = output_path(directory, &node.path())?;
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut entry_reader = cache_pair.open_entry(node)?;
+    let mut writer = BufWriter::new(File::create(&output_path)?);
+    // Copy block by block rather than through `Read`, which would turn the
+    // decompression errors into I/O errors
+    entry_reader.for_each_block(|data| Ok(writer.write_all(data)?))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Returns the path on disk of a file, refusing any TOC name that would
+/// escape the output directory.
+fn output_path(directory: &Path, path: &Path) -> Result<PathBuf> {
+    let mut output_path = directory.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::RootDir => continue,
+            Component::Normal(name) => output_path.push(name),
+            _ => return Err(Error::InvalidPath(path.to_path_buf())),
+        }
+    }
+    Ok(output_path)
+}
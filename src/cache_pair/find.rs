@@ -0,0 +1,125 @@
+use std::path::{Component, Path};
+
+use globset::{GlobBuilder, GlobMatcher};
+#[cfg(feature = "regex")]
+use regex::{Regex, RegexBuilder};
+
+use crate::toc::Node;
+use crate::{Error, Result};
+
+/// The syntax of a search pattern.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatternSyntax {
+    /// A glob pattern matched against the whole path.
+    ///
+    /// `*` and `?` do not match `/`, `**` matches any number of directories and `{a,b}` matches
+    /// either alternative. Since TOC paths are absolute, a pattern without any directory such as
+    /// `*.wav` matches nothing, `**/*.wav` matches the files of any directory.
+    #[default]
+    Glob,
+
+    /// A regular expression matching any part of the path, unless anchored with `^` and `$`.
+    #[cfg(feature = "regex")]
+    Regex,
+}
+
+/// Options for searching files in a cache pair.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FindOptions {
+    /// The syntax of the pattern.
+    pub syntax: PatternSyntax,
+
+    /// Whether the pattern matches regardless of the case.
+    pub case_insensitive: bool,
+}
+
+/// A compiled search pattern.
+pub(crate) enum Matcher {
+    /// A glob, along with the directory holding every path it can match, if
+    /// known.
+    Glob(GlobMatcher, Option<String>),
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, options: &FindOptions) -> Result<Self> {
+        match options.syntax {
+            PatternSyntax::Glob => GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .backslash_escape(true)
+                .case_insensitive(options.case_insensitive)
+                .build()
+                .map(|glob| {
+                    // The case of the directory names must match to look it up
+                    let base_directory = match options.case_insensitive {
+                        true => None,
+                        false => base_directory(pattern),
+                    };
+                    Matcher::Glob(glob.compile_matcher(), base_directory)
+                })
+                .map_err(|error| Error::InvalidPattern(error.to_string())),
+            #[cfg(feature = "regex")]
+            PatternSyntax::Regex => RegexBuilder::new(pattern)
+                .case_insensitive(options.case_insensitive)
+                .build()
+                .map(Matcher::Regex)
+                .map_err(|error| Error::InvalidPattern(error.to_string())),
+        }
+    }
+
+    /// Returns the directory holding every path the pattern can match, if
+    /// known, so that the rest of the tree does not need to be searched.
+    pub fn base_directory(&self) -> Option<&str> {
+        match self {
+            Matcher::Glob(_, base_directory) => base_directory.as_deref(),
+            #[cfg(feature = "regex")]
+            Matcher::Regex(_) => None,
+        }
+    }
+
+    pub fn is_match(&self, node: &Node) -> bool {
+        let path = toc_path_string(&node.path());
+        match self {
+            Matcher::Glob(glob, _) => glob.is_match(path),
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => regex.is_match(&path),
+        }
+    }
+}
+
+/// Returns the TOC path joined with `/` regardless of the platform separator.
+fn toc_path_string(path: &Path) -> String {
+    let mut path_string = String::new();
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            path_string.push('/');
+            path_string.push_str(&name.to_string_lossy());
+        }
+    }
+    if path_string.is_empty() {
+        path_string.push('/');
+    }
+    path_string
+}
+
+/// Returns the leading directories of an absolute glob pattern that do not
+/// contain any special character.
+fn base_directory(pattern: &str) -> Option<String> {
+    if !pattern.starts_with('/') {
+        return None;
+    }
+
+    // The last component is the file name, which is never a directory
+    let (directories, _) = pattern.rsplit_once('/')?;
+    let literal_len = directories
+        .split('/')
+        .take_while(|name| !name.contains(['*', '?', '[', ']', '{', '}', '\\']))
+        .map(|name| name.len() + 1)
+        .sum::<usize>();
+
+    match directories.get(..literal_len.saturating_sub(1)) {
+        Some("") | None => None,
+        Some(base_directory) => Some(base_directory.to_string()),
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::cache_pair::entry_reader::EntryReader;
+use crate::Result;
+
+/// A hash algorithm for the decompressed data of the files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// The 128-bit XXH3 hash, fast but not cryptographic.
+    #[default]
+    Xxh3,
+
+    /// The 256-bit BLAKE3 cryptographic hash.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+/// The hash of the decompressed data of a file.
+///
+/// Displayed as lowercase hexadecimal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ContentHash {
+    /// A 128-bit XXH3 hash.
+    Xxh3(u128),
+
+    /// A 256-bit BLAKE3 hash.
+    #[cfg(feature = "blake3")]
+    Blake3([u8; 32]),
+}
+
+impl ContentHash {
+    /// Returns the algorithm of the hash.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        match self {
+            Self::Xxh3(_) => HashAlgorithm::Xxh3,
+            #[cfg(feature = "blake3")]
+            Self::Blake3(_) => HashAlgorithm::Blake3,
+        }
+    }
+
+    /// Returns the bytes of the hash, big-endian for XXH3.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Xxh3(hash) => hash.to_be_bytes().to_vec(),
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hash) => hash.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.to_bytes() {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+enum Hasher {
+    Xxh3(Box<Xxh3>),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::default()),
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => Self::Blake3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Xxh3(hasher) => hasher.update(data),
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finish(self) -> ContentHash {
+        match self {
+            Self::Xxh3(hasher) => ContentHash::Xxh3(hasher.digest128()),
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => ContentHash::Blake3(*hasher.finalize().as_bytes()),
+        }
+    }
+}
+
+/// Hashes the decompressed data of the entry, one block at a time.
+pub(super) fn hash_entry(
+    entry: &mut EntryReader<'_>,
+    algorithm: HashAlgorithm,
+) -> Result<ContentHash> {
+    let mut hasher = Hasher::new(algorithm);
+    entry.for_each_block(|data| {
+        hasher.update(data);
+        Ok(())
+    })?;
+    Ok(hasher.finish())
+}
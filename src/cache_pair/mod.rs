@@ -1,13 +1,32 @@
 /*!
 
 This module provides functionality to read and parse the cache pairs from the `Cache.Windows`
-directory. Note that a cache pair is a pair of `.toc` and `.cache` files that contain information
-about the contents of a package and the compressed data for the package, respectively.
+directory, and to write new ones. Note that a cache pair is a pair of `.toc` and `.cache` files
+that contain information about the contents of a package and the compressed data for the package,
+respectively.
 
 */
 
 mod cache_pair;
 mod cache_pair_reader;
+mod cache_pair_writer;
+mod compact;
+mod diff;
+mod entry_reader;
+mod extract;
+mod find;
+mod hash;
+mod space;
+mod verify;
 
 pub use cache_pair::CachePair;
 pub use cache_pair_reader::CachePairReader;
+pub use cache_pair_writer::CachePairWriter;
+pub use compact::CompactReport;
+pub use diff::{diff, diff_with, DiffFile, DiffOptions, FileChange, ModifiedFile, MovedFile, TocDiff};
+pub use entry_reader::EntryReader;
+pub use extract::{ExtractOptions, ExtractProgress, ExtractReport};
+pub use find::{FindOptions, PatternSyntax};
+pub use hash::{ContentHash, HashAlgorithm};
+pub use space::{Extent, Overlap, SpaceReport, SpaceUsage};
+pub use verify::{CorruptEntry, VerifyReport};
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::toc::TocEntry;
+use crate::{Error, Result};
+
+/// The space used by a set of files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpaceUsage {
+    /// The number of files.
+    pub files: usize,
+
+    /// The total compressed length of the files, that is the bytes they reference in the cache
+    /// file.
+    pub comp_len: u64,
+
+    /// The total decompressed length of the files.
+    pub len: u64,
+}
+
+impl SpaceUsage {
+    /// Returns the compressed length divided by the decompressed length, or 1 if there is no data.
+    pub fn compression_ratio(&self) -> f64 {
+        match self.len {
+            0 => 1.0,
+            len => self.comp_len as f64 / len as f64,
+        }
+    }
+
+    fn add(&mut self, entry: &TocEntry) {
+        self.files += 1;
+        self.comp_len += entry.comp_len.max(0) as u64;
+        self.len += entry.len.max(0) as u64;
+    }
+}
+
+/// A range of bytes of the cache file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Extent {
+    /// The offset of the range.
+    pub offset: u64,
+
+    /// The length of the range.
+    pub len: u64,
+}
+
+/// Two files whose data overlap in the cache file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Overlap {
+    /// The TOC path of the file whose data starts first.
+    pub first: PathBuf,
+
+    /// Whether the first file has been replaced by a newer one.
+    pub first_is_replaced: bool,
+
+    /// The TOC path of the other file.
+    pub second: PathBuf,
+
+    /// Whether the other file has been replaced by a newer one.
+    pub second_is_replaced: bool,
+
+    /// The bytes shared by both files.
+    pub extent: Extent,
+}
+
+/// The space usage of a cache pair, as returned by
+/// [`CachePairReader::space_report`](crate::cache_pair::CachePairReader::space_report).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpaceReport {
+    /// The length of the cache file.
+    pub cache_len: u64,
+
+    /// The files of the TOC.
+    pub live: SpaceUsage,
+
+    /// The replaced versions of the files, which have a timestamp of 0.
+    pub replaced: SpaceUsage,
+
+    /// The bytes of the cache file that no live file references, which would be reclaimed by
+    /// rewriting the cache pair with only the live files.
+    pub reclaimable_len: u64,
+
+    /// The ranges of the cache file that no file references, live or replaced, in order.
+    pub gaps: Vec<Extent>,
+
+    /// The files whose data overlap, by order of their offset.
+    ///
+    /// Each file whose data starts within the data of another file is reported once, along with
+    /// the file reaching the furthest among those starting before it.
+    pub overlaps: Vec<Overlap>,
+
+    /// The space used by the live files of each directory, not including its subdirectories.
+    pub directories: BTreeMap<PathBuf, SpaceUsage>,
+
+    /// The space used by the live files of each lowercase extension, empty for the files without
+    /// any.
+    pub extensions: BTreeMap<String, SpaceUsage>,
+}
+
+impl SpaceReport {
+    /// Returns the total length of the gaps.
+    pub fn gaps_len(&self) -> u64 {
+        self.gaps.iter().map(|gap| gap.len).sum()
+    }
+}
+
+/// The data of a file, along with its path.
+struct FileExtent<'a> {
+    path: &'a Path,
+    is_replaced: bool,
+    offset: u64,
+    end: u64,
+}
+
+pub(super) fn space_report(entries: &[TocEntry], cache_len: u64) -> Result<SpaceReport> {
+    let mut report = SpaceReport {
+        cache_len,
+        ..Default::default()
+    };
+
+    // Full paths of the directories, indexed like the parent directory
+    // indices, following how the TOC is read
+    let mut directory_paths = vec![PathBuf::from("/")];
+    let mut files = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let is_replaced = entry.timestamp == 0;
+        if entry.is_directory() && is_replaced {
+            continue;
+        }
+
+        let name = entry
+            .name()
+            .map_err(|source| Error::InvalidEntryName { index, source })?;
+        let parent_index = usize::try_from(entry.parent_dir_index)
+            .ok()
+            .filter(|&parent_index| parent_index < directory_paths.len())
+            .ok_or(Error::ParentIndexOutOfRange {
+                index,
+                parent_index: entry.parent_dir_index,
+            })?;
+
+        if entry.is_directory() {
+            directory_paths.push(directory_paths[parent_index].join(name));
+            continue;
+        }
+
+        if is_replaced {
+            report.replaced.add(entry);
+        } else {
+            report.live.add(entry);
+
+            let directory = &directory_paths[parent_index];
+            match report.directories.get_mut(directory) {
+                Some(usage) => usage.add(entry),
+                None => {
+                    let mut usage = SpaceUsage::default();
+                    usage.add(entry);
+                    report.directories.insert(directory.clone(), usage);
+                }
+            }
+
+            let extension = match name.rsplit_once('.') {
+                Some((_, extension)) => extension.to_lowercase(),
+                None => String::new(),
+            };
+            report.extensions.entry(extension).or_default().add(entry);
+        }
+
+        files.push((entry, directory_paths[parent_index].join(name)));
+    }
+
+    // Empty files and invalid ranges do not reference any byte
+    let mut extents: Vec<_> = files
+        .iter()
+        .filter_map(|(entry, path)| {
+            let offset = u64::try_from(entry.cache_offset).ok()?;
+            let comp_len = u64::try_from(entry.comp_len).ok().filter(|&len| len > 0)?;
+            Some(FileExtent {
+                path,
+                is_replaced: entry.timestamp == 0,
+                offset,
+                end: offset + comp_len,
+            })
+        })
+        .collect();
+    extents.sort_by_key(|extent| (extent.offset, extent.end));
+
+    let mut live_len = 0;
+    let mut live_end = 0;
+    let mut end = 0;
+    let mut furthest_extent: Option<&FileExtent> = None;
+    for extent in &extents {
+        if extent.offset > end {
+            report.gaps.push(Extent {
+                offset: end,
+                len: extent.offset - end,
+            });
+        }
+
+        match furthest_extent {
+            Some(furthest_extent) if extent.offset < end => report.overlaps.push(Overlap {
+                first: furthest_extent.path.to_path_buf(),
+                first_is_replaced: furthest_extent.is_replaced,
+                second: extent.path.to_path_buf(),
+                second_is_replaced: extent.is_replaced,
+                extent: Extent {
+                    offset: extent.offset,
+                    len: extent.end.min(end) - extent.offset,
+                },
+            }),
+            _ => {}
+        }
+
+        if extent.end > end {
+            end = extent.end;
+            furthest_extent = Some(extent);
+        }
+
+        // Count the bytes referenced by the live files only once
+        if !extent.is_replaced && extent.end > live_end {
+            live_len += extent.end - extent.offset.max(live_end);
+            live_end = extent.end;
+        }
+    }
+    if cache_len > end {
+        report.gaps.push(Extent {
+            offset: end,
+            len: cache_len - end,
+        });
+    }
+    report.reclaimable_len = cache_len.saturating_sub(live_len);
+
+    Ok(report)
+}
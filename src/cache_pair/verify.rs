@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::cache_pair::entry_reader::{CacheSource, EntryReader};
+use crate::toc::{FileNode, NodeKind, TocEntry};
+use crate::{Error, Result};
+
+/// The outcome of verifying a cache pair.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// The number of TOC entries checked, including the directories and the replaced entries.
+    pub checked: usize,
+
+    /// The length of the truncated entry at the end of the TOC file, 0 if there is none.
+    pub truncated_len: usize,
+
+    /// The entries that failed at least one check, in the order of the TOC.
+    pub corrupt: Vec<CorruptEntry>,
+}
+
+impl VerifyReport {
+    /// Returns whether every check passed.
+    pub fn is_success(&self) -> bool {
+        self.truncated_len == 0 && self.corrupt.is_empty()
+    }
+}
+
+/// A TOC entry that failed at least one check.
+#[derive(Debug)]
+pub struct CorruptEntry {
+    /// The index of the entry in the TOC.
+    pub index: usize,
+
+    /// The TOC path of the entry, or only its name if its parent directory is invalid.
+    ///
+    /// Invalid UTF-8 sequences in the names are replaced with `U+FFFD`.
+    pub path: PathBuf,
+
+    /// The kind of the entry.
+    pub kind: NodeKind,
+
+    /// Whether the entry has been replaced by a newer one, see
+    /// [`CachePairReader::replaced_versions`](crate::cache_pair::CachePairReader::replaced_versions).
+    pub is_replaced: bool,
+
+    /// The failed checks.
+    pub errors: Vec<Error>,
+}
+
+/// The data location of a TOC entry.
+struct EntryData {
+    cache_offset: i64,
+    timestamp: i64,
+    comp_len: i32,
+    len: i32,
+}
+
+impl FileNode for EntryData {
+    fn cache_offset(&self) -> i64 {
+        self.cache_offset
+    }
+
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    fn comp_len(&self) -> i32 {
+        self.comp_len
+    }
+
+    fn len(&self) -> i32 {
+        self.len
+    }
+}
+
+pub(super) fn verify_entries(
+    entries: &[TocEntry],
+    truncated_len: usize,
+    cache_path: &Path,
+    mapped_cache: Option<&[u8]>,
+    is_post_ensmallening: bool,
+) -> Result<VerifyReport> {
+    let cache_file = match mapped_cache {
+        Some(_) => None,
+        None => Some(File::open(cache_path)?),
+    };
+    let cache_len = match &cache_file {
+        Some(cache_file) => cache_file.metadata()?.len(),
+        None => mapped_cache.map_or(0, |cache| cache.len() as u64),
+    };
+
+    let mut report = VerifyReport {
+        checked: entries.len(),
+        truncated_len,
+        corrupt: Vec::new(),
+    };
+
+    // Full paths of the directories, indexed like the parent directory
+    // indices, following how the TOC is read
+    let mut directory_paths = vec![PathBuf::from("/")];
+
+    for (index, entry) in entries.iter().enumerate() {
+        let mut errors = Vec::new();
+        let is_replaced = entry.timestamp == 0;
+
+        let name = match entry.name() {
+            Ok(name) => name.to_string(),
+            Err(source) => {
+                errors.push(Error::InvalidEntryName { index, source });
+                let null_index = entry.name.iter().position(|&x| x == 0);
+                String::from_utf8_lossy(&entry.name[..null_index.unwrap_or(entry.name.len())])
+                    .into_owned()
+            }
+        };
+
+        let parent_path = usize::try_from(entry.parent_dir_index)
+            .ok()
+            .and_then(|parent_index| directory_paths.get(parent_index));
+        let path = match parent_path {
+            Some(parent_path) => parent_path.join(&name),
+            None => {
+                errors.push(Error::ParentIndexOutOfRange {
+                    index,
+                    parent_index: entry.parent_dir_index,
+                });
+                PathBuf::from(&name)
+            }
+        };
+
+        let kind = if entry.is_directory() {
+            // Replaced directories are not part of the tree
+            if !is_replaced {
+                directory_paths.push(path.clone());
+            }
+            NodeKind::Directory
+        } else {
+            let data = EntryData {
+                cache_offset: entry.cache_offset,
+                timestamp: entry.timestamp,
+                comp_len: entry.comp_len,
+                len: entry.len,
+            };
+            // The cache file is opened once, each entry reader gets its own
+            // handle to it
+            let cache_source = match &cache_file {
+                Some(cache_file) => cache_file.try_clone().map(CacheSource::File),
+                None => Ok(CacheSource::Mapped(Cursor::new(
+                    mapped_cache.unwrap_or_default(),
+                ))),
+            };
+            match cache_source {
+                Ok(cache_source) => errors.extend(verify_data(
+                    index,
+                    &data,
+                    cache_source,
+                    cache_len,
+                    is_post_ensmallening,
+                )),
+                Err(error) => errors.push(error.into()),
+            }
+            NodeKind::File
+        };
+
+        if !errors.is_empty() {
+            report.corrupt.push(CorruptEntry {
+                index,
+                path,
+                kind,
+                is_replaced,
+                errors,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+/// Checks that the data of the file is within the cache file, that its block
+/// chain covers exactly its lengths, and that it decompresses.
+fn verify_data(
+    index: usize,
+    data: &EntryData,
+    cache_source: CacheSource<'_>,
+    cache_len: u64,
+    is_post_ensmallening: bool,
+) -> Option<Error> {
+    if data.len < 0 {
+        return Some(Error::InvalidEntryLength {
+            index: Some(index),
+            len: data.len,
+        });
+    }
+
+    let is_in_bounds = u64::try_from(data.cache_offset)
+        .ok()
+        .zip(u64::try_from(data.comp_len).ok())
+        .is_some_and(|(cache_offset, comp_len)| cache_offset + comp_len <= cache_len);
+    if !is_in_bounds {
+        return Some(Error::EntryOutOfBounds {
+            index: Some(index),
+            cache_offset: data.cache_offset,
+            comp_len: data.comp_len,
+            cache_len,
+        });
+    }
+
+    let mut entry_reader = match EntryReader::new(cache_source, data, is_post_ensmallening) {
+        Ok(entry_reader) => entry_reader,
+        Err(error) => return Some(error),
+    };
+
+    // The blocks are contiguous, so the chain ends where the last block does
+    let blocks = entry_reader.blocks();
+    let comp_end = blocks.last().map_or(data.cache_offset as u64, |block| {
+        block.offset + block.comp_len as u64
+    });
+    let comp_len = comp_end - data.cache_offset as u64;
+    let decomp_len: usize = blocks.iter().map(|block| block.decomp_len).sum();
+    if comp_len != data.comp_len as u64 || decomp_len != data.len as usize {
+        return Some(Error::CorruptBlockHeader(format!(
+            "Blocks cover {} compressed and {} decompressed bytes, \
+            expected {} and {}",
+            comp_len, decomp_len, data.comp_len, data.len
+        )));
+    }
+
+    entry_reader.for_each_block(|_| Ok(())).err()
+}
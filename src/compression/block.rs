@@ -0,0 +1,199 @@
+use log::debug;
+
+#[cfg(feature = "oodle")]
+use crate::compression::decompressor::OodleDecompressor;
+use crate::compression::decompressor::{registered_decompressor, Decompressor, Lz4Decompressor};
+use crate::compression::lz::compress_lz;
+#[cfg(feature = "oodle")]
+use crate::compression::oodle::compress_oodle;
+use crate::{Error, Result};
+
+/// The maximum compressed length of a single post-ensmallening block.
+pub(crate) const MAX_BLOCK_LEN: usize = 0x40000;
+
+/// The length of a post-ensmallening block header.
+pub(crate) const BLOCK_HEADER_LEN: usize = 8;
+
+/// The first byte of an Oodle compressed block.
+pub(crate) const OODLE_MAGIC: u8 = 0x8C;
+
+/// The largest chunk that always fits in a single block once compressed with
+/// LZ4, accounting for the worst case expansion and the prepended size.
+const LZ4_SAFE_CHUNK_LEN: usize = MAX_BLOCK_LEN - MAX_BLOCK_LEN / 255 - 32;
+
+/// The codec a block of cache data is stored with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    /// The block is compressed with Oodle.
+    Oodle,
+
+    /// The block is compressed with LZ4, with its decompressed size prepended.
+    Lz4,
+
+    /// The block is stored without compression.
+    Stored,
+}
+
+/// The location and size of a block of cache data.
+///
+/// Post-ensmallening entries are split into blocks of at most 256 KiB once compressed, each
+/// preceded by an 8-byte header. Pre-ensmallening entries are made of a single block without any
+/// header. Entries stored as is have no headers either, and are split into contiguous blocks of at
+/// most 256 KiB so that they are read in bounded chunks.
+///
+/// Returned by [`CachePairReader::blocks`](crate::cache_pair::CachePairReader::blocks).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// The offset of the block data in the cache file, past the block header if any.
+    pub offset: u64,
+
+    /// The compressed length of the block.
+    pub comp_len: usize,
+
+    /// The decompressed length of the block.
+    pub decomp_len: usize,
+
+    /// The codec the block is stored with.
+    pub codec: Codec,
+}
+
+/// Decompresses a single block into `decompressed_data`.
+///
+/// `compressed_data` and `decompressed_data` must have the compressed and
+/// decompressed lengths of the block respectively. The backend registered for
+/// the codec is used if any, otherwise the built-in one.
+pub(crate) fn decompress_block(
+    codec: Codec,
+    compressed_data: &[u8],
+    decompressed_data: &mut [u8],
+) -> Result<()> {
+    if let Some(decompressor) = registered_decompressor(codec) {
+        debug!(
+            "Decompressing with the registered {:?} backend ({} bytes)",
+            codec,
+            compressed_data.len()
+        );
+        return decompressor.decompress(compressed_data, decompressed_data);
+    }
+
+    match codec {
+        #[cfg(feature = "oodle")]
+        Codec::Oodle => {
+            debug!("Decompressing with oodle ({} bytes)", compressed_data.len());
+            OodleDecompressor.decompress(compressed_data, decompressed_data)
+        }
+        #[cfg(not(feature = "oodle"))]
+        Codec::Oodle => Err(Error::MissingDecompressor(codec)),
+        Codec::Lz4 => {
+            debug!("Decompressing with lz4 ({} bytes)", compressed_data.len());
+            Lz4Decompressor.decompress(compressed_data, decompressed_data)
+        }
+        Codec::Stored => {
+            debug!("Copying ({} bytes)", compressed_data.len());
+            if compressed_data.len() != decompressed_data.len() {
+                return Err(Error::CorruptBlockHeader(format!(
+                    "Stored block length mismatch, compressed_len: {}, decompressed_len: {}",
+                    compressed_data.len(),
+                    decompressed_data.len()
+                )));
+            }
+            decompressed_data.copy_from_slice(compressed_data);
+            Ok(())
+        }
+    }
+}
+
+/// Compresses a single chunk of data into a block.
+///
+/// The preferred codec is used if the resulting block is unambiguous when
+/// read back, otherwise the chunk is stored as is or compressed with LZ4.
+/// Returns `None` if no codec can encode this exact chunk, in which case a
+/// shorter chunk must be used.
+fn encode_block(codec: Codec, chunk: &[u8]) -> Result<Option<(Codec, Vec<u8>)>> {
+    let mut candidates = vec![codec, Codec::Stored, Codec::Lz4];
+    candidates.dedup();
+
+    for candidate in candidates {
+        let block_data = match candidate {
+            #[cfg(feature = "oodle")]
+            Codec::Oodle => compress_oodle(chunk)?,
+            #[cfg(not(feature = "oodle"))]
+            Codec::Oodle => return Err(Error::UnsupportedCodec(candidate)),
+            Codec::Lz4 => compress_lz(chunk),
+            Codec::Stored => chunk.to_vec(),
+        };
+
+        if is_unambiguous_block(candidate, chunk, &block_data) {
+            return Ok(Some((candidate, block_data)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns whether a block would be decoded with the same codec it was
+/// encoded with.
+///
+/// Readers detect Oodle blocks by their first byte and stored blocks by their
+/// compressed length being equal to their decompressed length.
+fn is_unambiguous_block(codec: Codec, chunk: &[u8], block_data: &[u8]) -> bool {
+    if block_data.len() > MAX_BLOCK_LEN {
+        return false;
+    }
+
+    let is_oodle_magic = block_data.first() == Some(&OODLE_MAGIC);
+    match codec {
+        Codec::Oodle => is_oodle_magic,
+        Codec::Lz4 => !is_oodle_magic && block_data.len() != chunk.len(),
+        Codec::Stored => !is_oodle_magic,
+    }
+}
+
+/// Returns the header preceding a post-ensmallening block.
+pub(crate) fn block_header(comp_len: usize, decomp_len: usize) -> [u8; BLOCK_HEADER_LEN] {
+    let num1 = 0x80000000 | ((comp_len as u32 & 0xFFFFFF) << 2);
+    let num2 = ((decomp_len as u32 & 0xFFFFFF) << 5) | 0x1;
+
+    let mut header = [0u8; BLOCK_HEADER_LEN];
+    header[..4].copy_from_slice(&num1.to_be_bytes());
+    header[4..].copy_from_slice(&num2.to_be_bytes());
+    header
+}
+
+/// Splits the data into blocks and compresses each of them, preceded by its
+/// header.
+pub(crate) fn encode_blocks(codec: Codec, data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoded_data = Vec::with_capacity(data.len());
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let mut chunk_len = (data.len() - pos).min(MAX_BLOCK_LEN);
+
+        let (block_codec, block_data) = loop {
+            if let Some(block) = encode_block(codec, &data[pos..pos + chunk_len])? {
+                break block;
+            }
+
+            // Shrink the chunk until it can be encoded, first to a length LZ4
+            // can always fit in a block, then byte by byte to change the
+            // prepended size that could be mistaken for the Oodle magic
+            chunk_len = if chunk_len > LZ4_SAFE_CHUNK_LEN {
+                LZ4_SAFE_CHUNK_LEN
+            } else {
+                chunk_len - 1
+            };
+        };
+        debug!(
+            "Encoded block with {:?}, compressed_len: {}, decompressed_len: {}",
+            block_codec,
+            block_data.len(),
+            chunk_len
+        );
+
+        encoded_data.extend_from_slice(&block_header(block_data.len(), chunk_len));
+        encoded_data.extend_from_slice(&block_data);
+        pos += chunk_len;
+    }
+
+    Ok(encoded_data)
+}
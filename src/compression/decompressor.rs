@@ -0,0 +1,102 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::compression::block::Codec;
+use crate::compression::lz::decompress_lz;
+#[cfg(feature = "oodle")]
+use crate::compression::oodle::decompress_oodle;
+use crate::Result;
+
+/// A decoder for the blocks of a codec.
+///
+/// Backends are registered for a codec with [`register_decompressor`], and are then used for
+/// every block stored with that codec, in place of the built-in one if any. Closures with the
+/// signature of [`Decompressor::decompress`] implement this trait.
+pub trait Decompressor: Send + Sync {
+    /// Decompresses a block.
+    ///
+    /// `decompressed_data` has the decompressed length of the block and must be filled entirely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the block is corrupt or does not decompress to the expected length.
+    fn decompress(&self, compressed_data: &[u8], decompressed_data: &mut [u8]) -> Result<()>;
+}
+
+impl<F> Decompressor for F
+where
+    F: Fn(&[u8], &mut [u8]) -> Result<()> + Send + Sync,
+{
+    fn decompress(&self, compressed_data: &[u8], decompressed_data: &mut [u8]) -> Result<()> {
+        self(compressed_data, decompressed_data)
+    }
+}
+
+/// The built-in LZ4 backend, for blocks with their decompressed size prepended.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lz4Decompressor;
+
+impl Decompressor for Lz4Decompressor {
+    fn decompress(&self, compressed_data: &[u8], decompressed_data: &mut [u8]) -> Result<()> {
+        decompress_lz(
+            compressed_data,
+            compressed_data.len(),
+            decompressed_data,
+            decompressed_data.len(),
+        )
+    }
+}
+
+/// The built-in Oodle backend, which links to the Oodle shared library.
+#[cfg(feature = "oodle")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OodleDecompressor;
+
+#[cfg(feature = "oodle")]
+impl Decompressor for OodleDecompressor {
+    fn decompress(&self, compressed_data: &[u8], decompressed_data: &mut [u8]) -> Result<()> {
+        decompress_oodle(
+            compressed_data,
+            compressed_data.len(),
+            decompressed_data,
+            decompressed_data.len(),
+        )
+    }
+}
+
+/// The backends registered at runtime, indexed by [`slot`].
+static REGISTRY: RwLock<[Option<Arc<dyn Decompressor>>; 3]> = RwLock::new([None, None, None]);
+
+fn slot(codec: Codec) -> usize {
+    match codec {
+        Codec::Oodle => 0,
+        Codec::Lz4 => 1,
+        Codec::Stored => 2,
+    }
+}
+
+/// Registers a backend for the blocks of the given codec, for the whole process.
+///
+/// The backend replaces the built-in one, if any, along with the backend previously registered for
+/// this codec, which is returned. This allows decompressing Oodle blocks without the `oodle`
+/// feature, for example with a pure Rust decoder, or mocking a codec in tests.
+pub fn register_decompressor(
+    codec: Codec,
+    decompressor: Arc<dyn Decompressor>,
+) -> Option<Arc<dyn Decompressor>> {
+    let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+    registry[slot(codec)].replace(decompressor)
+}
+
+/// Unregisters the backend registered for the given codec, if any, and returns it.
+///
+/// The built-in backend of the codec, if any, is used again.
+pub fn unregister_decompressor(codec: Codec) -> Option<Arc<dyn Decompressor>> {
+    let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+    registry[slot(codec)].take()
+}
+
+/// Returns the backend registered for the given codec, if any.
+pub(crate) fn registered_decompressor(codec: Codec) -> Option<Arc<dyn Decompressor>> {
+    let registry = REGISTRY.read().unwrap_or_else(PoisonError::into_inner);
+    registry[slot(codec)].clone()
+}
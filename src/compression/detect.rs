@@ -0,0 +1,78 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::compression::block::{BLOCK_HEADER_LEN, MAX_BLOCK_LEN};
+use crate::compression::post_ensmallening::{get_block_lengths, is_oodle_block};
+use crate::Result;
+
+/// The maximum number of compressed entries sampled to detect the format of a
+/// cache file.
+const SAMPLE_COUNT: usize = 16;
+
+/// Detects whether the entries of a cache file are compressed in the
+/// post-ensmallening format by sampling the first compressed ones.
+///
+/// The entries are given as `(cache_offset, compressed_len, decompressed_len)`.
+/// Returns `None` if no sampled entry is conclusive, such as when every entry
+/// is stored uncompressed.
+pub(crate) fn detect_post_ensmallening<R, I>(cache_reader: &mut R, entries: I) -> Option<bool>
+where
+    R: Read + Seek,
+    I: IntoIterator<Item = (u64, usize, usize)>,
+{
+    let mut post_count = 0;
+    let mut pre_count = 0;
+
+    entries
+        .into_iter()
+        // Stored entries have no header, and smaller entries are ambiguous
+        .filter(|&(_, comp_len, len)| comp_len != len && comp_len >= BLOCK_HEADER_LEN)
+        .take(SAMPLE_COUNT)
+        .for_each(|(cache_offset, comp_len, len)| {
+            match sample_entry(cache_reader, cache_offset, comp_len, len) {
+                Ok(Some(true)) => post_count += 1,
+                Ok(Some(false)) => pre_count += 1,
+                // Unreadable entries are not conclusive either
+                Ok(None) | Err(_) => {}
+            }
+        });
+
+    match (post_count, pre_count) {
+        (0, 0) => None,
+        _ => Some(post_count >= pre_count),
+    }
+}
+
+/// Returns whether a single compressed entry looks post-ensmallening, or
+/// `None` if it looks like both formats or neither.
+fn sample_entry<R: Read + Seek>(
+    cache_reader: &mut R,
+    cache_offset: u64,
+    comp_len: usize,
+    len: usize,
+) -> Result<Option<bool>> {
+    cache_reader.seek(SeekFrom::Start(cache_offset))?;
+
+    // Post-ensmallening entries start with a block header, or directly with
+    // an Oodle block for single block entries
+    let is_post = match get_block_lengths(cache_reader)? {
+        Some((block_comp_len, block_decomp_len)) => {
+            block_decomp_len > 0
+                && block_decomp_len <= len.min(MAX_BLOCK_LEN)
+                && block_comp_len <= (comp_len - BLOCK_HEADER_LEN).min(MAX_BLOCK_LEN)
+        }
+        None => is_oodle_block(cache_reader)?,
+    };
+
+    // Pre-ensmallening entries are LZ4 blocks prepended with their
+    // little-endian decompressed length
+    let mut size_prefix = [0u8; 4];
+    cache_reader.seek(SeekFrom::Start(cache_offset))?;
+    cache_reader.read_exact(&mut size_prefix)?;
+    let is_pre = u32::from_le_bytes(size_prefix) as usize == len;
+
+    Ok(match (is_post, is_pre) {
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        _ => None,
+    })
+}
@@ -1,5 +1,7 @@
-use anyhow::Result;
-use lz4_flex::decompress_size_prepended;
+use lz4_flex::block::DecompressError;
+use lz4_flex::{compress_prepend_size, decompress_size_prepended};
+
+use crate::{Error, Result};
 
 pub fn decompress_lz(
     compressed_data: &[u8],
@@ -7,7 +9,28 @@ pub fn decompress_lz(
     decompressed_data: &mut [u8],
     decompressed_len: usize,
 ) -> Result<()> {
-    let decompressed_data_buffer = decompress_size_prepended(&compressed_data[..compressed_len])?;
+    let compressed_data = compressed_data
+        .get(..compressed_len)
+        .ok_or(DecompressError::ExpectedAnotherByte)?;
+    if decompressed_data.len() < decompressed_len {
+        return Err(Error::Lz4(DecompressError::OutputTooSmall {
+            expected: decompressed_len,
+            actual: decompressed_data.len(),
+        }));
+    }
+
+    let decompressed_data_buffer = decompress_size_prepended(compressed_data)?;
+    if decompressed_data_buffer.len() != decompressed_len {
+        return Err(Error::Lz4(DecompressError::UncompressedSizeDiffers {
+            expected: decompressed_len,
+            actual: decompressed_data_buffer.len(),
+        }));
+    }
+
     decompressed_data[..decompressed_len].copy_from_slice(&decompressed_data_buffer);
     Ok(())
 }
+
+pub fn compress_lz(decompressed_data: &[u8]) -> Vec<u8> {
+    compress_prepend_size(decompressed_data)
+}
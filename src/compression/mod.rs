@@ -1,19 +1,38 @@
 /*!
 
 This module provides functions to decompress data compressed with the Oodle or LZ compression
-algorithms.
+algorithms, and to compress data in the formats of the cache files.
+
+Blocks are decompressed with the built-in backend of their codec, unless a [`Decompressor`] is
+registered for it with [`register_decompressor`]. The built-in Oodle backend requires the `oodle`
+feature, which is enabled by default, as does compressing with Oodle.
+
+Behind the `post_ensmallening` feature, `compress_post_ensmallening` splits data into blocks
+compressed with LZ4 or Oodle, each preceded by its header. Behind the `pre_ensmallening` feature,
+`compress_pre_ensmallening` compresses data with LZ4, prepended with its decompressed size.
 
 */
 
+pub(crate) mod block;
+mod decompressor;
+pub(crate) mod detect;
 mod lz;
+#[cfg(feature = "oodle")]
 mod oodle;
 pub(crate) mod post_ensmallening;
 pub(crate) mod pre_ensmallening;
 
+pub use block::{BlockInfo, Codec};
+#[cfg(feature = "oodle")]
+pub use decompressor::OodleDecompressor;
+pub use decompressor::{
+    register_decompressor, unregister_decompressor, Decompressor, Lz4Decompressor,
+};
+
 #[cfg(feature = "post_ensmallening")]
-pub use post_ensmallening::decompress_post_ensmallening;
+pub use post_ensmallening::{compress_post_ensmallening, decompress_post_ensmallening};
 #[cfg(feature = "pre_ensmallening")]
-pub use pre_ensmallening::decompress_pre_ensmallening;
+pub use pre_ensmallening::{compress_pre_ensmallening, decompress_pre_ensmallening};
 
 #[cfg(feature = "internal")]
 pub use post_ensmallening::get_block_lengths;
@@ -1,5 +1,6 @@
-use anyhow::{Error, Result};
-use oodle_safe::decompress;
+use oodle_safe::{compress, decompress, CompressionLevel, Compressor};
+
+use crate::{Error, Result};
 
 pub fn decompress_oodle(
     compressed_data: &[u8],
@@ -7,13 +8,38 @@ pub fn decompress_oodle(
     decompressed_data: &mut [u8],
     decompressed_len: usize,
 ) -> Result<()> {
-    let input = &compressed_data[..compressed_len];
-    let output = &mut decompressed_data[..decompressed_len];
+    let input = compressed_data.get(..compressed_len).ok_or(Error::Oodle)?;
+    let output = decompressed_data
+        .get_mut(..decompressed_len)
+        .ok_or(Error::Oodle)?;
 
     let result = decompress(input, output, None, None, None, None);
 
     match result {
-        Err(_) => Err(Error::msg("Failed to decompress oodle data")),
+        Err(_) => Err(Error::Oodle),
         Ok(_) => Ok(()),
     }
 }
+
+pub fn compress_oodle(decompressed_data: &[u8]) -> Result<Vec<u8>> {
+    // Leave room for the Oodle block headers of incompressible data
+    let mut compressed_data = vec![0u8; decompressed_data.len() + 0x200];
+
+    let result = compress(
+        Compressor::Kraken,
+        decompressed_data,
+        &mut compressed_data,
+        CompressionLevel::Normal,
+        None,
+        None,
+        None,
+    );
+
+    match result {
+        Err(_) | Ok(0) => Err(Error::Oodle),
+        Ok(compressed_len) => {
+            compressed_data.truncate(compressed_len);
+            Ok(compressed_data)
+        }
+    }
+}
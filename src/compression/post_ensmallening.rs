@@ -1,97 +1,171 @@
 use std::cmp::min_by;
-use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 
-use anyhow::Result;
 use log::debug;
 
-use crate::compression::lz::decompress_lz;
-use crate::compression::oodle::decompress_oodle;
-
-pub fn decompress_post_ensmallening(
+use crate::compression::block::{
+    decompress_block, encode_blocks, BlockInfo, Codec, BLOCK_HEADER_LEN, MAX_BLOCK_LEN, OODLE_MAGIC,
+};
+use crate::{Error, Result};
+
+/// Decompresses a post-ensmallening entry read from the current position of the cache reader.
+///
+/// The entry is made of blocks of at most 256 KiB once compressed, each preceded by its header,
+/// except for single block entries stored directly as an Oodle block.
+///
+/// # Errors
+///
+/// Returns an error if the data cannot be read, if the block headers do not cover exactly the
+/// given lengths, or if a block fails to decompress.
+pub fn decompress_post_ensmallening<R: Read + Seek>(
     compressed_len: usize,
     decompressed_len: usize,
-    cache_reader: &mut File,
+    cache_reader: &mut R,
 ) -> Result<Vec<u8>> {
+    let blocks = get_blocks(compressed_len, decompressed_len, cache_reader)?;
+
     let mut decompressed_data = vec![0u8; decompressed_len];
-    let mut compressed_buffer = vec![0u8; 0x40000];
+    let mut compressed_buffer = vec![0u8; MAX_BLOCK_LEN];
+    let mut decompressed_pos = 0;
+
+    for block in blocks {
+        cache_reader.seek(SeekFrom::Start(block.offset))?;
+        cache_reader.read_exact(&mut compressed_buffer[..block.comp_len])?;
+
+        decompress_block(
+            block.codec,
+            &compressed_buffer[..block.comp_len],
+            &mut decompressed_data[decompressed_pos..decompressed_pos + block.decomp_len],
+        )?;
+        debug!("Decompressed {} bytes", block.decomp_len);
+        decompressed_pos += block.decomp_len;
+    }
+
+    Ok(decompressed_data)
+}
+
+/// Compresses the data into post-ensmallening blocks, preferably with the given codec.
+///
+/// The data is split into blocks of at most 256 KiB once compressed, each preceded by an 8-byte
+/// big-endian header holding its compressed and decompressed lengths. Blocks that would be
+/// ambiguous when read back, or that do not fit in a block once compressed, are stored or
+/// compressed with LZ4 instead, so the output always decodes with
+/// [`decompress_post_ensmallening`].
+///
+/// Readers take data whose compressed length equals its decompressed length for stored data, in
+/// which case the data must be stored as is instead, like [`CachePairWriter`] does.
+///
+/// [`CachePairWriter`]: crate::cache_pair::CachePairWriter
+///
+/// # Errors
+///
+/// Returns [`Error::UnsupportedCodec`] if the codec is Oodle without the `oodle` feature, or
+/// [`Error::Oodle`] if Oodle fails to compress a block.
+pub fn compress_post_ensmallening(
+    codec: Codec,
+    decompressed_data: &[u8],
+) -> Result<Vec<u8>> {
+    encode_blocks(codec, decompressed_data)
+}
+
+/// Walks the block headers starting at the current position of the reader
+/// until `decompressed_len` bytes are covered.
+///
+/// The reader is left at the end of the last block.
+pub(crate) fn get_blocks<R: Read + Seek>(
+    compressed_len: usize,
+    decompressed_len: usize,
+    cache_reader: &mut R,
+) -> Result<Vec<BlockInfo>> {
+    let mut blocks = Vec::new();
     let mut decompressed_pos = 0;
 
-    let cache_len = cache_reader.metadata()?.len() as usize;
+    let start_offset = cache_reader.stream_position()?;
+    let cache_len = cache_reader.seek(SeekFrom::End(0))? as usize;
+    cache_reader.seek(SeekFrom::Start(start_offset))?;
 
     while decompressed_pos < decompressed_len {
         let (block_comp_len, block_decomp_len) =
             get_block_lengths(cache_reader)?.unwrap_or((compressed_len, decompressed_len));
         debug!(
-            "Decompressing block, compressed_len: {}, decompressed_len: {}",
+            "Found block, compressed_len: {}, decompressed_len: {}",
             block_comp_len, block_decomp_len
         );
 
+        if block_decomp_len == 0 {
+            return Err(Error::CorruptBlockHeader(String::from(
+                "Block has a decompressed length of 0",
+            )));
+        }
+
         if decompressed_pos + block_decomp_len > decompressed_len {
-            return Err(anyhow::anyhow!(
+            return Err(Error::CorruptBlockHeader(format!(
                 "Decompressed past the file length, \
                 decompressed_pos: {}, decompressed_len: {}, file_len: {}",
-                decompressed_pos,
-                block_decomp_len,
-                decompressed_len
-            ));
+                decompressed_pos, block_decomp_len, decompressed_len
+            )));
         }
 
-        let cache_offset = cache_reader.seek(SeekFrom::Current(0))? as usize;
-        let remaining_len = cache_len - cache_offset;
-        if block_comp_len > min_by(remaining_len, 0x40000, |a, b| a.cmp(b)) {
-            return Err(anyhow::anyhow!(
+        let cache_offset = cache_reader.stream_position()?;
+        let remaining_len = cache_len.saturating_sub(cache_offset as usize);
+        if block_comp_len > min_by(remaining_len, MAX_BLOCK_LEN, |a, b| a.cmp(b)) {
+            return Err(Error::CorruptBlockHeader(format!(
                 "Tried to read beyond limits, probably not a compressed file, \
                 compressed_len: {}, remaining_len: {}",
-                block_comp_len,
-                remaining_len
-            ));
+                block_comp_len, remaining_len
+            )));
         }
 
-        let is_oodle = is_oodle_block(cache_reader)?;
-        cache_reader.read_exact(&mut compressed_buffer[..block_comp_len])?;
-
-        if is_oodle {
-            debug!("Decompressing with oodle ({} bytes)", block_comp_len);
-            decompress_oodle(
-                &compressed_buffer,
-                block_comp_len,
-                &mut decompressed_data[decompressed_pos as usize..],
-                block_decomp_len,
-            )?;
+        let codec = if is_oodle_block(cache_reader)? {
+            Codec::Oodle
         } else if block_comp_len == block_decomp_len {
-            debug!("Copying ({} bytes)", block_comp_len);
-            decompressed_data[decompressed_pos as usize..decompressed_pos + block_decomp_len]
-                .copy_from_slice(&compressed_buffer[..block_comp_len]);
+            Codec::Stored
         } else {
-            debug!("Decompressing with lz4 ({} bytes)", block_comp_len);
-            decompress_lz(
-                &compressed_buffer,
-                block_comp_len,
-                &mut decompressed_data[decompressed_pos as usize..],
-                block_decomp_len,
-            )?;
-        }
-        debug!("Decompressed {} bytes", block_decomp_len);
+            Codec::Lz4
+        };
+
+        blocks.push(BlockInfo {
+            offset: cache_offset,
+            comp_len: block_comp_len,
+            decomp_len: block_decomp_len,
+            codec,
+        });
+
+        cache_reader.seek(SeekFrom::Current(block_comp_len as i64))?;
         decompressed_pos += block_decomp_len;
     }
 
-    Ok(decompressed_data)
+    Ok(blocks)
 }
 
-pub fn is_oodle_block(cache_reader: &mut File) -> Result<bool> {
+/// Returns whether the data at the current position of the cache reader starts with the Oodle
+/// magic byte. The position is left unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the byte cannot be read.
+pub fn is_oodle_block<R: Read + Seek>(cache_reader: &mut R) -> Result<bool> {
     let mut check_magic = [0u8; 1];
     cache_reader.by_ref().read_exact(&mut check_magic)?;
     cache_reader.seek(SeekFrom::Current(-1))?;
-    Ok(check_magic[0] == 0x8C)
+    Ok(check_magic[0] == OODLE_MAGIC)
 }
 
-pub fn get_block_lengths(cache_reader: &mut File) -> Result<Option<(usize, usize)>> {
-    let mut block_info = [0u8; 8];
+/// Reads the block header at the current position of the cache reader, and returns the
+/// compressed and decompressed lengths of the block.
+///
+/// Returns `None` if the data does not start with a block header, in which case the position is
+/// left unchanged. Otherwise the position is moved past the header.
+///
+/// # Errors
+///
+/// Returns an error if the header cannot be read.
+pub fn get_block_lengths<R: Read + Seek>(cache_reader: &mut R) -> Result<Option<(usize, usize)>> {
+    let mut block_info = [0u8; BLOCK_HEADER_LEN];
     cache_reader.read_exact(&mut block_info)?;
 
     if block_info[0] != 0x80 || (block_info[7] & 0x0F) != 0x1 {
-        cache_reader.seek(SeekFrom::Current(-8))?;
+        cache_reader.seek(SeekFrom::Current(-(BLOCK_HEADER_LEN as i64)))?;
         return Ok(None);
     }
 
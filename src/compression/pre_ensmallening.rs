@@ -1,26 +1,37 @@
-use std::fs::File;
 use std::io::Read;
 
-use anyhow::Result;
+use crate::compression::block::{decompress_block, Codec};
+use crate::compression::lz::compress_lz;
+use crate::Result;
 
-use crate::compression::lz::decompress_lz;
-
-pub fn decompress_pre_ensmallening(
+/// Decompresses a pre-ensmallening entry read from the current position of the cache reader.
+///
+/// The entry is a single LZ4 block prepended with its decompressed size.
+///
+/// # Errors
+///
+/// Returns an error if the data cannot be read or fails to decompress to the given length.
+pub fn decompress_pre_ensmallening<R: Read>(
     compressed_len: usize,
     decompressed_len: usize,
-    cache_reader: &mut File,
+    cache_reader: &mut R,
 ) -> Result<Vec<u8>> {
     let mut compressed_data = vec![0u8; compressed_len];
     let mut decompressed_data = vec![0u8; decompressed_len];
 
-    cache_reader.read_exact(&mut compressed_data).unwrap();
+    cache_reader.read_exact(&mut compressed_data)?;
 
-    decompress_lz(
-        &compressed_data,
-        compressed_len,
-        &mut decompressed_data,
-        decompressed_len,
-    )?;
+    decompress_block(Codec::Lz4, &compressed_data, &mut decompressed_data)?;
 
     Ok(decompressed_data)
 }
+
+/// Compresses the data with LZ4, prepended with its decompressed size, which always decodes with
+/// [`decompress_pre_ensmallening`].
+///
+/// Readers take data whose compressed length equals its decompressed length for stored data, in
+/// which case the data must be stored as is instead, like
+/// [`CachePairWriter`](crate::cache_pair::CachePairWriter) does.
+pub fn compress_pre_ensmallening(decompressed_data: &[u8]) -> Vec<u8> {
+    compress_lz(decompressed_data)
+}
@@ -0,0 +1,152 @@
+/*!
+
+This module defines the [`Error`] type returned by every fallible function of this crate, along
+with the [`Result`] alias.
+
+*/
+
+use std::io;
+use std::path::PathBuf;
+use std::str::Utf8Error;
+
+use lz4_flex::block::DecompressError;
+use thiserror::Error;
+
+use crate::cache_pair::VerifyReport;
+use crate::compression::Codec;
+
+/// A specialized [`Result`](std::result::Result) type for lotus-lib operations.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The error type for reading and decompressing cache pairs.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// An I/O error occurred while reading a `.toc` or `.cache` file.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// The TOC file does not start with the expected magic number.
+    #[error("invalid TOC magic number: {0:#010X}")]
+    InvalidMagic(u32),
+
+    /// The TOC file has an archive version that is not supported.
+    #[error(
+        "unsupported TOC archive version: {0}, expected {}",
+        crate::toc::ARCHIVE_VERSION
+    )]
+    UnsupportedVersion(u32),
+
+    /// The TOC file ends in the middle of an entry.
+    #[error("truncated TOC entry at index {index}")]
+    TruncatedTocEntry {
+        /// The index of the incomplete entry.
+        index: usize,
+    },
+
+    /// A TOC entry references a parent directory that does not exist.
+    #[error("TOC entry {index} references out of range parent directory {parent_index}")]
+    ParentIndexOutOfRange {
+        /// The index of the entry.
+        index: usize,
+        /// The parent directory index stored in the entry.
+        parent_index: i32,
+    },
+
+    /// A TOC entry name is not valid UTF-8.
+    #[error("TOC entry {index} has an invalid name: {source}")]
+    InvalidEntryName {
+        /// The index of the entry.
+        index: usize,
+        /// The underlying UTF-8 error.
+        source: Utf8Error,
+    },
+
+    /// The data of a TOC entry lies outside of the `.cache` file.
+    #[error(
+        "TOC entry{} data ({comp_len} bytes at offset {cache_offset}) is out of the {cache_len} \
+        bytes cache file",
+        .index.map(|index| format!(" {index}")).unwrap_or_default()
+    )]
+    EntryOutOfBounds {
+        /// The index of the entry in the TOC file, if known.
+        index: Option<usize>,
+        /// The offset of the data stored in the entry.
+        cache_offset: i64,
+        /// The compressed length stored in the entry.
+        comp_len: i32,
+        /// The length of the cache file.
+        cache_len: u64,
+    },
+
+    /// A TOC entry has a negative compressed or decompressed length.
+    #[error(
+        "TOC entry{} has an invalid length: {len}",
+        .index.map(|index| format!(" {index}")).unwrap_or_default()
+    )]
+    InvalidEntryLength {
+        /// The index of the entry in the TOC file, if known.
+        index: Option<usize>,
+        /// The invalid length stored in the entry.
+        len: i32,
+    },
+
+    /// A byte range is out of the decompressed data of a file.
+    #[error("invalid range {start}..{end} of a {len} bytes file")]
+    InvalidRange {
+        /// The start of the range.
+        start: u64,
+        /// The end of the range.
+        end: u64,
+        /// The decompressed length of the file.
+        len: u64,
+    },
+
+    /// A compressed block header is inconsistent with the entry it belongs to.
+    #[error("corrupt block header: {0}")]
+    CorruptBlockHeader(String),
+
+    /// A block is stored with a codec that has no decompressor, see
+    /// [`register_decompressor`](crate::compression::register_decompressor).
+    #[error("no decompressor for {0:?} blocks, enable its feature or register one")]
+    MissingDecompressor(Codec),
+
+    /// Oodle failed to decompress a block.
+    #[error("failed to decompress oodle data")]
+    Oodle,
+
+    /// LZ4 failed to decompress a block.
+    #[error("failed to decompress lz4 data: {0}")]
+    Lz4(#[from] DecompressError),
+
+    /// The TOC of a cache pair must be read before this operation.
+    #[error("TOC not read: {0}")]
+    TocNotRead(PathBuf),
+
+    /// A path cannot be added to a cache pair.
+    #[error("invalid cache pair path: {0}")]
+    InvalidPath(PathBuf),
+
+    /// A compacted cache pair failed verification, so it has been removed.
+    #[error(
+        "compacted cache pair failed verification with {} corrupt entries",
+        .0.corrupt.len()
+    )]
+    UnverifiedCompaction(Box<VerifyReport>),
+
+    /// A search pattern cannot be compiled.
+    #[error("invalid search pattern: {0}")]
+    InvalidPattern(String),
+
+    /// A codec cannot be used with the cache pair format, or its encoder is not compiled in.
+    #[error("unsupported codec: {0:?}")]
+    UnsupportedCodec(Codec),
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(error) => error,
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
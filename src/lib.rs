@@ -8,11 +8,26 @@ data in a directory-like structure.
 
 ## Dependencies
 
-This library depends on `oodle-sys` which is a wrapper around
-`liboo2corelinux64.so` which in turn needs to be installed on your system.
-You can get it by following the instructions here:
+With the default `oodle` feature, this library depends on `oodle-sys` which
+is a wrapper around `liboo2corelinux64.so` which in turn needs to be installed
+on your system. You can get it by following the instructions here:
 <https://github.com/sehnryr/get-oodle-lib>
 
+Without it, Oodle blocks can only be decompressed by registering a backend
+with `compression::register_decompressor`.
+
+## Features
+
+- `oodle` (default): Decompresses and compresses Oodle blocks with the Oodle shared library.
+- `mmap`: Allows memory-mapping the `.cache` files with `CachePairReader::map_cache` so that they
+  are opened only once and stored entries can be borrowed without copying.
+- `regex`: Allows searching the TOC paths with regular expressions in addition to glob patterns,
+  see `CachePairReader::find_with`.
+- `blake3`: Allows hashing the decompressed data of the files with BLAKE3 in addition to XXH3,
+  see `CachePairReader::hash_entry`.
+- `serde`: Allows serializing the reports of `cache_pair::diff` and
+  `CachePairReader::space_report` with `serde`, for example to JSON.
+
 ## Credits
 
 This library is based on the work of [LotusLib](https://github.com/Puxtril/LotusLib)
@@ -22,5 +37,8 @@ by [Puxtril](https://github.com/Puxtril). The original library is written in C++
 
 pub mod cache_pair;
 pub mod compression;
+mod error;
 pub mod package;
 pub mod toc;
+
+pub use error::{Error, Result};
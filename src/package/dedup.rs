@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::cache_pair::{CachePairReader, ContentHash, HashAlgorithm};
+use crate::package::package_collection::PackageCollection;
+use crate::package::package_type::PackageType;
+use crate::toc::{FileNode, Node};
+use crate::{Error, Result};
+
+/// Options for grouping the identical files of a package collection.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DedupOptions {
+    /// The algorithm hashing the decompressed data of the files.
+    pub algorithm: HashAlgorithm,
+
+    /// The number of threads hashing files.
+    ///
+    /// Defaults to the available parallelism of the system.
+    pub threads: Option<NonZeroUsize>,
+}
+
+/// The location of a file in a package collection.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntryLocation {
+    /// The name of the package.
+    pub package_name: String,
+
+    /// The type of the cache pair within the package.
+    pub package_type: PackageType,
+
+    /// The TOC path of the file.
+    pub path: PathBuf,
+}
+
+/// The files of a package collection sharing the same decompressed data.
+#[derive(Debug, Clone)]
+pub struct Blob {
+    /// The hash of the data.
+    pub hash: ContentHash,
+
+    /// The decompressed length of the data.
+    pub len: u64,
+
+    /// The files holding the data, in the order of the collection.
+    pub entries: Vec<EntryLocation>,
+}
+
+/// The outcome of grouping the identical files of a package collection.
+#[derive(Debug, Default)]
+pub struct DedupReport {
+    /// Every distinct data, in the order of the first file holding it.
+    pub blobs: Vec<Blob>,
+
+    /// The files that could not be hashed, along with the error.
+    pub failed: Vec<(EntryLocation, Error)>,
+}
+
+impl DedupReport {
+    /// Returns whether every file has been hashed.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns the data held by more than one file.
+    pub fn duplicates(&self) -> impl Iterator<Item = &Blob> {
+        self.blobs.iter().filter(|blob| blob.entries.len() > 1)
+    }
+
+    /// Returns the number of decompressed bytes saved by storing each data only once.
+    pub fn saved_len(&self) -> u64 {
+        self.duplicates()
+            .map(|blob| blob.len * (blob.entries.len() as u64 - 1))
+            .sum()
+    }
+}
+
+pub(super) fn dedup_files(
+    collection: &PackageCollection<CachePairReader>,
+    options: &DedupOptions,
+) -> DedupReport {
+    let mut files: Vec<(&String, PackageType, &CachePairReader, &Node)> = Vec::new();
+    for package in collection.packages() {
+        for package_type in [PackageType::H, PackageType::F, PackageType::B] {
+            let Some(cache_pair) = package.borrow(package_type) else {
+                continue;
+            };
+            for node in cache_pair.files() {
+                files.push((package.name(), package_type, cache_pair, node));
+            }
+        }
+    }
+    let total = files.len();
+
+    let threads = options
+        .threads
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(total.max(1));
+
+    let next_index = AtomicUsize::new(0);
+    let mut hashes: Vec<(usize, Result<ContentHash>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut hashes = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::Relaxed);
+                        let Some(&(_, _, cache_pair, node)) = files.get(index) else {
+                            break hashes;
+                        };
+                        hashes.push((index, cache_pair.hash_entry(node, options.algorithm)));
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+            })
+            .collect()
+    });
+    // Report the files in the order of the collection
+    hashes.sort_by_key(|(index, _)| *index);
+
+    let mut report = DedupReport::default();
+    let mut blob_indices = HashMap::new();
+    for (index, hash) in hashes {
+        let (package_name, package_type, _, node) = files[index];
+        let location = EntryLocation {
+            package_name: package_name.clone(),
+            package_type,
+            path: node.path(),
+        };
+
+        match hash {
+            Ok(hash) => {
+                let len = node.len() as u64;
+                let blob_index = *blob_indices.entry((hash, len)).or_insert_with(|| {
+                    report.blobs.push(Blob {
+                        hash,
+                        len,
+                        entries: Vec::new(),
+                    });
+                    report.blobs.len() - 1
+                });
+                report.blobs[blob_index].entries.push(location);
+            }
+            Err(error) => report.failed.push((location, error)),
+        }
+    }
+
+    report
+}
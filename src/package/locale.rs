@@ -0,0 +1,126 @@
+use std::fmt;
+
+/// Represents the language of a localized package
+///
+/// Localized packages are named after the package they localize, either with a language code
+/// suffix such as `Misc_fr`, or with a `Language` prefix such as `LanguageFR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Locale {
+    /// English (`en`)
+    English,
+    /// French (`fr`)
+    French,
+    /// German (`de`)
+    German,
+    /// Spanish (`es`)
+    Spanish,
+    /// Italian (`it`)
+    Italian,
+    /// Japanese (`ja`, also written `jp`)
+    Japanese,
+    /// Korean (`ko`)
+    Korean,
+    /// Polish (`pl`)
+    Polish,
+    /// Portuguese (`pt`)
+    Portuguese,
+    /// Russian (`ru`)
+    Russian,
+    /// Thai (`th`)
+    Thai,
+    /// Turkish (`tr`)
+    Turkish,
+    /// Ukrainian (`uk`)
+    Ukrainian,
+    /// Simplified Chinese (`zh`)
+    SimplifiedChinese,
+    /// Traditional Chinese (`tc`)
+    TraditionalChinese,
+}
+
+impl Locale {
+    /// Every supported locale
+    pub const ALL: [Locale; 15] = [
+        Self::English,
+        Self::French,
+        Self::German,
+        Self::Spanish,
+        Self::Italian,
+        Self::Japanese,
+        Self::Korean,
+        Self::Polish,
+        Self::Portuguese,
+        Self::Russian,
+        Self::Thai,
+        Self::Turkish,
+        Self::Ukrainian,
+        Self::SimplifiedChinese,
+        Self::TraditionalChinese,
+    ];
+
+    /// Returns the lowercase language code of the locale
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::French => "fr",
+            Self::German => "de",
+            Self::Spanish => "es",
+            Self::Italian => "it",
+            Self::Japanese => "ja",
+            Self::Korean => "ko",
+            Self::Polish => "pl",
+            Self::Portuguese => "pt",
+            Self::Russian => "ru",
+            Self::Thai => "th",
+            Self::Turkish => "tr",
+            Self::Ukrainian => "uk",
+            Self::SimplifiedChinese => "zh",
+            Self::TraditionalChinese => "tc",
+        }
+    }
+
+    /// Splits a package name into the name of the package it localizes and its locale
+    ///
+    /// Returns the name as is and `None` if the package is not localized.
+    pub(super) fn split_package_name(name: &str) -> (&str, Option<Locale>) {
+        if let Some((base_name, code)) = name.rsplit_once('_') {
+            match Locale::try_from(code) {
+                Ok(locale) if !base_name.is_empty() => return (base_name, Some(locale)),
+                _ => {}
+            }
+        }
+
+        if let Some(code) = name.strip_prefix("Language") {
+            if let Ok(locale) = Locale::try_from(code) {
+                return ("Language", Some(locale));
+            }
+        }
+
+        (name, None)
+    }
+}
+
+impl TryFrom<&str> for Locale {
+    type Error = &'static str;
+
+    /// Converts from a language code, regardless of its case
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the language code is unknown
+    fn try_from(code: &str) -> Result<Self, Self::Error> {
+        match code.to_ascii_lowercase().as_str() {
+            "jp" => Ok(Self::Japanese),
+            code => Self::ALL
+                .into_iter()
+                .find(|locale| locale.code() == code)
+                .ok_or("Invalid locale code"),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
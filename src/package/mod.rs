@@ -5,10 +5,18 @@ directory. The `Cache.Windows` directory contains the packages that store the da
 
 */
 
+mod dedup;
+mod locale;
 mod package;
 mod package_collection;
 mod package_type;
+mod residency;
+mod vfs;
 
+pub use dedup::{Blob, DedupOptions, DedupReport, EntryLocation};
+pub use locale::Locale;
 pub use package::Package;
 pub use package_collection::PackageCollection;
 pub use package_type::PackageType;
+pub use residency::{ResidencyStats, ResidentToc};
+pub use vfs::{Vfs, VfsEntry, VfsOptions};
@@ -1,14 +1,19 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::cache_pair::CachePair;
+use crate::cache_pair::{CachePair, CachePairReader, ExtractOptions, ExtractReport};
+use crate::toc::Node;
+use crate::Result;
 
+use super::locale::Locale;
 use super::package_type::PackageType;
 
 /// A package containing three cache pairs.
 pub struct Package<T: CachePair> {
     directory: PathBuf,
     name: String,
-    is_post_ensmallening: bool,
+    locale: Option<Locale>,
+    is_orphan: bool,
+    is_post_ensmallening: Option<bool>,
 
     h_package: Option<T>,
     f_package: Option<T>,
@@ -16,7 +21,7 @@ pub struct Package<T: CachePair> {
 }
 
 impl<T: CachePair> Package<T> {
-    pub(super) fn new<P>(directory: P, name: String, is_post_ensmallening: bool) -> Self
+    pub(super) fn new<P>(directory: P, name: String, is_post_ensmallening: Option<bool>) -> Self
     where
         P: Into<PathBuf>,
     {
@@ -25,9 +30,12 @@ impl<T: CachePair> Package<T> {
         let h_package = Package::<T>::new_package(&directory, &name, is_post_ensmallening, 'H');
         let f_package = Package::<T>::new_package(&directory, &name, is_post_ensmallening, 'F');
         let b_package = Package::<T>::new_package(&directory, &name, is_post_ensmallening, 'B');
+        let (_, locale) = Locale::split_package_name(&name);
         Self {
             directory,
             name,
+            locale,
+            is_orphan: h_package.is_none(),
             is_post_ensmallening,
             h_package,
             f_package,
@@ -37,11 +45,12 @@ impl<T: CachePair> Package<T> {
 
     /// Creates a new package from the specified directory, name, and type.
     ///
-    /// Returns `None` if the package does not exist.
+    /// Returns `None` if the package does not exist, or if its `.toc` or `.cache` file is missing
+    /// since its TOC could not be read.
     fn new_package<P, I>(
         directory: P,
         name: &str,
-        is_post_ensmallening: bool,
+        is_post_ensmallening: Option<bool>,
         trio_type: I,
     ) -> Option<T>
     where
@@ -59,7 +68,7 @@ impl<T: CachePair> Package<T> {
         toc_path.push(format!("{}.{}.toc", trio_type, name));
         cache_path.push(format!("{}.{}.cache", trio_type, name));
 
-        if !toc_path.exists() && !cache_path.exists() {
+        if !toc_path.exists() || !cache_path.exists() {
             return None;
         }
         Some(T::new(toc_path, cache_path, is_post_ensmallening))
@@ -75,11 +84,31 @@ impl<T: CachePair> Package<T> {
         &self.name
     }
 
-    /// Returns whether the package is post-ensmallening.
+    /// Returns the name of the package this package localizes.
+    ///
+    /// This is the name without its locale, such as `Misc` for `Misc_fr`, or the name itself if
+    /// the package is not localized.
+    pub fn base_name(&self) -> &str {
+        Locale::split_package_name(&self.name).0
+    }
+
+    /// Returns the locale of the package, if it is localized.
+    pub fn locale(&self) -> Option<Locale> {
+        self.locale
+    }
+
+    /// Returns whether the H cache pair of the package is missing, so that only its F or B cache
+    /// pairs exist.
+    pub fn is_orphan(&self) -> bool {
+        self.is_orphan
+    }
+
+    /// Returns whether the package is post-ensmallening, if overridden.
     ///
     /// This is used to determine how to decompress the data from before "The Great Ensmallening"
-    /// update of Warframe.
-    pub fn is_post_ensmallening(&self) -> bool {
+    /// update of Warframe. Returns `None` if the format is detected for each cache pair, see
+    /// [`CachePair::is_post_ensmallening`].
+    pub fn is_post_ensmallening(&self) -> Option<bool> {
         self.is_post_ensmallening
     }
 
@@ -126,3 +155,40 @@ impl<T: CachePair> Package<T> {
         }
     }
 }
+
+impl Package<CachePairReader> {
+    /// Extracts the files matching the filter from every cache pair of the package.
+    ///
+    /// The files of each cache pair are written to a subdirectory named after its type, such as
+    /// `H/Lotus/...`, since the same path usually exists in several cache pairs. The TOCs must have
+    /// been read. See [`CachePairReader::extract_to`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a TOC has not been read or if an output directory cannot be created.
+    pub fn extract_all<P, F>(
+        &self,
+        directory: P,
+        filter: F,
+        options: &ExtractOptions<'_>,
+    ) -> Result<Vec<(PackageType, ExtractReport)>>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Node) -> bool,
+    {
+        let directory = directory.as_ref();
+
+        let mut reports = Vec::new();
+        for package_type in [PackageType::H, PackageType::F, PackageType::B] {
+            let Some(cache_pair) = self.borrow(package_type) else {
+                continue;
+            };
+
+            let package_directory = directory.join(char::from(package_type).to_string());
+            let report = cache_pair.extract_to(package_directory, &filter, options)?;
+            reports.push((package_type, report));
+        }
+
+        Ok(reports)
+    }
+}
@@ -1,62 +1,79 @@
-use std::io;
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 
-use crate::cache_pair::CachePair;
+use crate::cache_pair::{CachePair, CachePairReader};
+use crate::package::dedup::{dedup_files, DedupOptions, DedupReport};
+use crate::package::locale::Locale;
 use crate::package::package::Package;
+use crate::package::package_type::PackageType;
+use crate::package::residency::{Residency, ResidencyStats, ResidentToc};
+use crate::package::vfs::{Vfs, VfsOptions};
+use crate::Result;
 
 /// A collection of packages.
 pub struct PackageCollection<T: CachePair> {
     directory: PathBuf,
-    is_post_ensmallening: bool,
+    is_post_ensmallening: Option<bool>,
     packages: Vec<Package<T>>,
+    residency: Residency,
 }
 
 impl<T: CachePair> PackageCollection<T> {
     /// Creates a new package collection from the specified directory.
     ///
+    /// Every package with at least one `.toc` or `.cache` file is listed, sorted by name, including
+    /// the packages missing their H cache pair (see [`Self::orphans`]) and the localized packages
+    /// (see [`Self::borrow_localized`]). A cache pair is only part of its package when both of its
+    /// files exist.
+    ///
+    /// The compression format of each cache pair is detected when its TOC is read, unless
+    /// `is_post_ensmallening` overrides it, so collections mixing both formats can be read.
+    ///
     /// # Errors
     ///
     /// Returns an error if the directory does not exist or if the directory cannot be read.
-    pub fn new<P>(directory: P, is_post_ensmallening: bool) -> Result<Self, io::Error>
+    pub fn new<P>(directory: P, is_post_ensmallening: Option<bool>) -> Result<Self>
     where
         P: Into<PathBuf>,
     {
         let directory = directory.into();
 
-        let mut packages = Vec::new();
-        for entry in std::fs::read_dir(&directory).unwrap() {
+        let mut package_names = BTreeSet::new();
+        for entry in std::fs::read_dir(&directory)? {
             let entry = entry?;
-            let file_name = entry.file_name().into_string().unwrap();
 
-            // Check if the file has enough characters to be a package 7 characters counts for the
-            // shortest possible package name of 1 character : H.1.toc
-            if file_name.len() < 7 {
-                continue;
-            }
+            // Package names are always valid UTF-8, skip any other file
+            let file_name = match entry.file_name().into_string() {
+                Ok(file_name) => file_name,
+                Err(_) => continue,
+            };
 
-            // Check if the file is a valid header .toc file
-            if !file_name.starts_with("H.") || !file_name.ends_with(".toc") {
-                continue;
+            // Packages are listed from any of their cache pair files, so that packages missing
+            // their H cache pair are found as well
+            if let Some(package_name) = package_name(&file_name) {
+                package_names.insert(package_name.to_string());
             }
-
-            let package_name = file_name[2..file_name.len() - 4].to_string();
-
-            let package = Package::<T>::new(&directory, package_name, is_post_ensmallening);
-            packages.push(package);
         }
 
+        let packages = package_names
+            .into_iter()
+            .map(|package_name| Package::<T>::new(&directory, package_name, is_post_ensmallening))
+            .collect();
+
         Ok(Self {
             directory,
             is_post_ensmallening,
             packages,
+            residency: Residency::default(),
         })
     }
 
-    /// Returns whether the package is post-ensmallening.
+    /// Returns whether the package collection is post-ensmallening, if overridden.
     ///
     /// This is used to determine how to decompress the data from before "The Great Ensmallening"
-    /// update of Warframe.
-    pub fn is_post_ensmallening(&self) -> bool {
+    /// update of Warframe. Returns `None` if the format is detected for each cache pair, see
+    /// [`CachePair::is_post_ensmallening`].
+    pub fn is_post_ensmallening(&self) -> Option<bool> {
         self.is_post_ensmallening
     }
 
@@ -80,9 +97,32 @@ impl<T: CachePair> PackageCollection<T> {
             .packages
             .iter()
             .position(|package| package.name() == package_name)?;
+        self.residency.remove_package(index);
         Some(self.packages.remove(index))
     }
 
+    /// Returns a reference to the variant of the package with the specified base name for the
+    /// specified locale if found.
+    ///
+    /// For example, the French variant of `Misc` is `Misc_fr`. See [`Package::base_name`].
+    pub fn borrow_localized(&self, base_name: &str, locale: Locale) -> Option<&Package<T>> {
+        self.packages
+            .iter()
+            .find(|package| package.base_name() == base_name && package.locale() == Some(locale))
+    }
+
+    /// Returns the package with the specified base name and all of its localized variants.
+    pub fn variants<'a>(&'a self, base_name: &'a str) -> impl Iterator<Item = &'a Package<T>> {
+        self.packages
+            .iter()
+            .filter(move |package| package.base_name() == base_name)
+    }
+
+    /// Returns the packages missing their H cache pair, which only have F or B cache pairs.
+    pub fn orphans(&self) -> impl Iterator<Item = &Package<T>> {
+        self.packages.iter().filter(|package| package.is_orphan())
+    }
+
     /// Returns the directory of the package collection.
     pub fn directory(&self) -> &PathBuf {
         &self.directory
@@ -93,3 +133,244 @@ impl<T: CachePair> PackageCollection<T> {
         &self.packages
     }
 }
+
+/// Returns the name of the package of a cache pair file, such as `Misc` for `H.Misc.toc`.
+fn package_name(file_name: &str) -> Option<&str> {
+    let stem = file_name
+        .strip_suffix(".toc")
+        .or_else(|| file_name.strip_suffix(".cache"))?;
+
+    match stem.split_once('.')? {
+        ("H" | "F" | "B", package_name) if !package_name.is_empty() => Some(package_name),
+        _ => None,
+    }
+}
+
+impl PackageCollection<CachePairReader> {
+    /// Returns a virtual filesystem merging the TOCs of every package.
+    ///
+    /// Packages take precedence in the order of the collection, and the cache pairs of a package
+    /// in the H, F, B order. The TOCs that are not resident yet are read first, see
+    /// [`Self::vfs_with`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a TOC cannot be read.
+    pub fn vfs(&mut self) -> Result<Vfs<'_>> {
+        self.vfs_with(VfsOptions::default())
+    }
+
+    /// Returns a virtual filesystem merging the TOCs of every package, with the given precedence
+    /// rules.
+    ///
+    /// The TOCs of the cache pairs in the view that are not resident yet are read first, and count
+    /// as accessed. The memory budget is not enforced while the view is borrowed, the TOCs over
+    /// budget are unloaded on the next access through [`Self::cache_pair`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a TOC cannot be read.
+    pub fn vfs_with(&mut self, options: VfsOptions) -> Result<Vfs<'_>> {
+        self.read_tocs(&options.package_types)?;
+        Ok(Vfs::new(self, options))
+    }
+
+    /// Groups the files of every cache pair by their decompressed data.
+    ///
+    /// Every file is streamed through the hash algorithm of the options with
+    /// [`CachePairReader::hash_entry`], in parallel, and files with the same hash and length are
+    /// grouped together. The TOCs that are not resident yet are read first, like
+    /// [`Self::vfs_with`] does.
+    ///
+    /// Errors related to a single file do not stop the pass, they are reported in the returned
+    /// [`DedupReport`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a TOC cannot be read.
+    pub fn dedup(&mut self, options: &DedupOptions) -> Result<DedupReport> {
+        self.read_tocs(&[PackageType::H, PackageType::F, PackageType::B])?;
+        Ok(dedup_files(self, options))
+    }
+
+    /// Returns the cache pair of the specified type of the package with the specified name, reading
+    /// its TOC if it is not resident yet.
+    ///
+    /// If a memory budget is set, the least recently accessed TOCs are unloaded until the resident
+    /// TOCs fit in it, see [`Self::set_memory_budget`].
+    ///
+    /// Returns `None` if the package or the cache pair does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOC cannot be read.
+    pub fn cache_pair<I>(
+        &mut self,
+        package_name: &str,
+        package_type: I,
+    ) -> Result<Option<&CachePairReader>>
+    where
+        I: TryInto<PackageType>,
+    {
+        let Ok(package_type) = package_type.try_into() else {
+            return Ok(None);
+        };
+        let Some(index) = self
+            .packages
+            .iter()
+            .position(|package| package.name() == package_name)
+        else {
+            return Ok(None);
+        };
+        let Some(cache_pair) = self.packages[index].borrow_mut(package_type) else {
+            return Ok(None);
+        };
+
+        if cache_pair.is_toc_loaded() {
+            self.residency.hits += 1;
+        } else {
+            cache_pair.read_toc()?;
+            self.residency.loads += 1;
+        }
+        self.residency.touch(index, package_type);
+
+        self.evict(Some((index, package_type)));
+
+        Ok(self.packages[index].borrow(package_type))
+    }
+
+    /// Returns the memory budget for the resident TOCs, in bytes.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.residency.memory_budget
+    }
+
+    /// Set the memory budget for the resident TOCs, in bytes.
+    ///
+    /// The memory used by a TOC is estimated with [`CachePairReader::toc_memory_usage`]. The least
+    /// recently accessed TOCs are unloaded right away until the resident TOCs fit in the budget,
+    /// and then on every access through [`Self::cache_pair`]. The TOC being accessed is never
+    /// unloaded, even if it does not fit in the budget on its own.
+    ///
+    /// TOCs read directly on a cache pair count as the least recently accessed.
+    pub fn set_memory_budget(&mut self, memory_budget: Option<usize>) {
+        self.residency.memory_budget = memory_budget;
+        self.evict(None);
+    }
+
+    /// Unloads every resident TOC.
+    pub fn unload_all(&mut self) {
+        for (index, package) in self.packages.iter_mut().enumerate() {
+            for package_type in [PackageType::H, PackageType::F, PackageType::B] {
+                if let Some(cache_pair) = package.borrow_mut(package_type) {
+                    cache_pair.unread_toc();
+                    self.residency.forget(index, package_type);
+                }
+            }
+        }
+    }
+
+    /// Returns statistics on the resident TOCs.
+    pub fn residency(&self) -> ResidencyStats {
+        let mut resident: Vec<_> = self
+            .resident_tocs()
+            .map(|(index, package_type, memory_usage)| {
+                let last_access = self.residency.last_access(index, package_type);
+                let resident_toc = ResidentToc {
+                    package_name: self.packages[index].name().clone(),
+                    package_type,
+                    memory_usage,
+                    is_tracked: last_access.is_some(),
+                };
+                (last_access, resident_toc)
+            })
+            .collect();
+        resident.sort_by_key(|(last_access, _)| *last_access);
+
+        ResidencyStats {
+            memory_usage: resident
+                .iter()
+                .map(|(_, resident_toc)| resident_toc.memory_usage)
+                .sum(),
+            resident: resident
+                .into_iter()
+                .map(|(_, resident_toc)| resident_toc)
+                .collect(),
+            memory_budget: self.residency.memory_budget,
+            hits: self.residency.hits,
+            loads: self.residency.loads,
+            evictions: self.residency.evictions,
+        }
+    }
+
+    /// Reads the TOCs of the given types that are not resident yet, without
+    /// enforcing the memory budget.
+    fn read_tocs(&mut self, package_types: &[PackageType]) -> Result<()> {
+        for (index, package) in self.packages.iter_mut().enumerate() {
+            for &package_type in package_types {
+                let Some(cache_pair) = package.borrow_mut(package_type) else {
+                    continue;
+                };
+
+                if cache_pair.is_toc_loaded() {
+                    self.residency.hits += 1;
+                } else {
+                    cache_pair.read_toc()?;
+                    self.residency.loads += 1;
+                }
+                self.residency.touch(index, package_type);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the package index, type and memory usage of every resident TOC.
+    fn resident_tocs(&self) -> impl Iterator<Item = (usize, PackageType, usize)> + '_ {
+        self.packages
+            .iter()
+            .enumerate()
+            .flat_map(|(index, package)| {
+                [PackageType::H, PackageType::F, PackageType::B]
+                    .into_iter()
+                    .filter_map(move |package_type| {
+                        let cache_pair = package.borrow(package_type)?;
+                        cache_pair
+                            .is_toc_loaded()
+                            .then(|| (index, package_type, cache_pair.toc_memory_usage()))
+                    })
+            })
+    }
+
+    /// Unloads the least recently accessed TOCs, except the given one, until
+    /// the resident TOCs fit in the memory budget.
+    fn evict(&mut self, keep: Option<(usize, PackageType)>) {
+        let Some(memory_budget) = self.residency.memory_budget else {
+            return;
+        };
+
+        let mut resident: Vec<_> = self
+            .resident_tocs()
+            .map(|(index, package_type, memory_usage)| {
+                let last_access = self.residency.last_access(index, package_type);
+                (last_access, index, package_type, memory_usage)
+            })
+            .collect();
+        resident.sort_by_key(|(last_access, ..)| *last_access);
+
+        let mut memory_usage: usize = resident.iter().map(|(.., memory_usage)| memory_usage).sum();
+        for (_, index, package_type, toc_memory_usage) in resident {
+            if memory_usage <= memory_budget {
+                break;
+            }
+            if keep == Some((index, package_type)) {
+                continue;
+            }
+
+            if let Some(cache_pair) = self.packages[index].borrow_mut(package_type) {
+                cache_pair.unread_toc();
+            }
+            self.residency.forget(index, package_type);
+            self.residency.evictions += 1;
+            memory_usage -= toc_memory_usage;
+        }
+    }
+}
@@ -1,5 +1,5 @@
 /// Represents the type of a package trio
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PackageType {
     /// Header package
     ///
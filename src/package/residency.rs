@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use super::package_type::PackageType;
+
+/// The TOCs resident in memory in a [`PackageCollection`](super::PackageCollection).
+#[derive(Debug, Clone, Default)]
+pub struct ResidencyStats {
+    /// The resident TOCs, from the least to the most recently accessed.
+    pub resident: Vec<ResidentToc>,
+
+    /// The estimated memory used by the resident TOCs, in bytes.
+    pub memory_usage: usize,
+
+    /// The memory budget of the collection, if any.
+    pub memory_budget: Option<usize>,
+
+    /// The number of accesses to a TOC that was already resident.
+    pub hits: u64,
+
+    /// The number of TOCs read on access.
+    pub loads: u64,
+
+    /// The number of TOCs unloaded to stay within the memory budget.
+    pub evictions: u64,
+}
+
+/// A TOC resident in memory.
+#[derive(Debug, Clone)]
+pub struct ResidentToc {
+    /// The name of the package.
+    pub package_name: String,
+
+    /// The type of the cache pair within the package.
+    pub package_type: PackageType,
+
+    /// The estimated memory used by the TOC, in bytes.
+    pub memory_usage: usize,
+
+    /// Whether the TOC has been accessed through the collection, as opposed to being read
+    /// directly on the cache pair.
+    pub is_tracked: bool,
+}
+
+/// The least recently used bookkeeping of a package collection.
+///
+/// TOCs are identified by the index of their package in the collection.
+#[derive(Default)]
+pub(super) struct Residency {
+    pub memory_budget: Option<usize>,
+    pub hits: u64,
+    pub loads: u64,
+    pub evictions: u64,
+    clock: u64,
+    last_access: HashMap<(usize, PackageType), u64>,
+}
+
+impl Residency {
+    /// Marks the TOC as the most recently accessed one.
+    pub fn touch(&mut self, package_index: usize, package_type: PackageType) {
+        self.clock += 1;
+        self.last_access
+            .insert((package_index, package_type), self.clock);
+    }
+
+    /// Forgets the last access of an unloaded TOC.
+    pub fn forget(&mut self, package_index: usize, package_type: PackageType) {
+        self.last_access.remove(&(package_index, package_type));
+    }
+
+    /// Forgets the TOCs of a package removed from the collection, and shifts
+    /// the indices of the packages after it.
+    pub fn remove_package(&mut self, package_index: usize) {
+        self.last_access = std::mem::take(&mut self.last_access)
+            .into_iter()
+            .filter(|&((index, _), _)| index != package_index)
+            .map(|((index, package_type), last_access)| {
+                let index = match index > package_index {
+                    true => index - 1,
+                    false => index,
+                };
+                ((index, package_type), last_access)
+            })
+            .collect();
+    }
+
+    /// Returns the last access of the TOC, if it has been accessed through the
+    /// collection.
+    pub fn last_access(&self, package_index: usize, package_type: PackageType) -> Option<u64> {
+        self.last_access
+            .get(&(package_index, package_type))
+            .copied()
+    }
+}
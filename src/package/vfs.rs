@@ -0,0 +1,186 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::cache_pair::CachePairReader;
+use crate::package::package::Package;
+use crate::package::package_collection::PackageCollection;
+use crate::package::package_type::PackageType;
+use crate::toc::{DirectoryNode, FileNode, Node, NodeKind};
+use crate::Result;
+
+/// The precedence rules of a [`Vfs`].
+#[derive(Clone, Debug)]
+pub struct VfsOptions {
+    /// The names of the packages by decreasing precedence.
+    ///
+    /// Packages that are not listed come after the listed ones, in the order of the collection.
+    pub package_order: Vec<String>,
+
+    /// The package types by decreasing precedence, within a package.
+    ///
+    /// Cache pairs of the types that are not listed are left out of the view.
+    pub package_types: Vec<PackageType>,
+
+    /// Whether the most recent file takes precedence regardless of the package order.
+    pub prefer_newest: bool,
+}
+
+impl Default for VfsOptions {
+    fn default() -> Self {
+        Self {
+            package_order: Vec::new(),
+            package_types: vec![PackageType::H, PackageType::F, PackageType::B],
+            prefer_newest: false,
+        }
+    }
+}
+
+/// A cache pair providing a path in a [`Vfs`].
+#[derive(Clone)]
+pub struct VfsEntry<'a> {
+    /// The package holding the cache pair.
+    pub package: &'a Package<CachePairReader>,
+
+    /// The type of the cache pair within the package.
+    pub package_type: PackageType,
+
+    /// The cache pair providing the path.
+    pub cache_pair: &'a CachePairReader,
+
+    /// The node of the path in the cache pair.
+    pub node: Node,
+}
+
+impl VfsEntry<'_> {
+    /// Read and decompress the data of the file.
+    ///
+    /// See [`CachePairReader::decompress_data`].
+    pub fn decompress_data(&self) -> Result<Vec<u8>> {
+        self.cache_pair.decompress_data(&self.node)
+    }
+}
+
+impl fmt::Debug for VfsEntry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VfsEntry")
+            .field("package", self.package.name())
+            .field("package_type", &self.package_type)
+            .field("node", &self.node)
+            .finish()
+    }
+}
+
+/// A virtual filesystem merging the TOCs of every package of a [`PackageCollection`].
+///
+/// The view does not copy the TOCs, each lookup queries the path index of every cache pair.
+///
+/// Created with [`PackageCollection::vfs`], which reads the TOCs that are not resident yet.
+pub struct Vfs<'a> {
+    /// The cache pairs of the view by decreasing precedence, ignoring the
+    /// timestamps.
+    cache_pairs: Vec<(
+        &'a Package<CachePairReader>,
+        PackageType,
+        &'a CachePairReader,
+    )>,
+    prefer_newest: bool,
+}
+
+impl<'a> Vfs<'a> {
+    pub(super) fn new(
+        collection: &'a PackageCollection<CachePairReader>,
+        options: VfsOptions,
+    ) -> Self {
+        let package_rank = |package: &Package<CachePairReader>| {
+            options
+                .package_order
+                .iter()
+                .position(|name| name == package.name())
+                .unwrap_or(options.package_order.len())
+        };
+
+        let mut packages: Vec<_> = collection.packages().iter().collect();
+        // The sort is stable, so unlisted packages keep the collection order
+        packages.sort_by_key(|package| package_rank(package));
+
+        let cache_pairs = packages
+            .into_iter()
+            .flat_map(|package| {
+                options
+                    .package_types
+                    .iter()
+                    .filter_map(move |&package_type| {
+                        let cache_pair = package.borrow(package_type)?;
+                        Some((package, package_type, cache_pair))
+                    })
+            })
+            .collect();
+
+        Self {
+            cache_pairs,
+            prefer_newest: options.prefer_newest,
+        }
+    }
+
+    /// Returns every cache pair providing the given path, by decreasing precedence.
+    pub fn lookup<T: Into<PathBuf>>(&self, path: T) -> Vec<VfsEntry<'a>> {
+        let path = path.into();
+
+        let mut entries: Vec<_> = self
+            .cache_pairs
+            .iter()
+            .filter_map(|&(package, package_type, cache_pair)| {
+                let node = cache_pair
+                    .get_file_node(&path)
+                    .or_else(|| cache_pair.get_directory_node(&path))?;
+                Some(VfsEntry {
+                    package,
+                    package_type,
+                    cache_pair,
+                    node,
+                })
+            })
+            .collect();
+
+        if self.prefer_newest {
+            entries.sort_by_key(|entry| Reverse(entry.node.timestamp()));
+        }
+
+        entries
+    }
+
+    /// Returns the file with the highest precedence at the given path.
+    pub fn get_file<T: Into<PathBuf>>(&self, path: T) -> Option<VfsEntry<'a>> {
+        self.lookup(path)
+            .into_iter()
+            .find(|entry| entry.node.kind() == NodeKind::File)
+    }
+
+    /// Returns whether any cache pair provides the given path.
+    pub fn exists<T: AsRef<Path>>(&self, path: T) -> bool {
+        let path = path.as_ref();
+        self.cache_pairs.iter().any(|&(_, _, cache_pair)| {
+            cache_pair.get_file_node(path).is_some()
+                || cache_pair.get_directory_node(path).is_some()
+        })
+    }
+
+    /// Returns the merged children of the directory at the given path, sorted by name.
+    ///
+    /// The kind of each child is the one of the cache pair with the highest precedence providing
+    /// it.
+    pub fn read_dir<T: Into<PathBuf>>(&self, path: T) -> Vec<(String, NodeKind)> {
+        let mut children = BTreeMap::new();
+        for entry in self.lookup(path) {
+            if entry.node.kind() != NodeKind::Directory {
+                continue;
+            }
+            for child in entry.node.children() {
+                children.entry(child.name()).or_insert_with(|| child.kind());
+            }
+        }
+        children.into_iter().collect()
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+use std::path::{Component, Path, PathBuf};
+
+use crate::toc::node::{FileNode, NodeKind};
+use crate::toc::toc::read_toc_entries;
+use crate::{Error, Result};
+
+/// The index of the root directory in the arena.
+const ROOT_INDEX: u32 = 0;
+
+/// A compact and immutable representation of a TOC.
+///
+/// Unlike the tree of [`Node`](crate::toc::Node)s, the entries are stored in a single flat vector
+/// with the index of their parent, and their names are interned in a shared string table. The
+/// children of a directory are contiguous and sorted by name, so that they are found with a binary
+/// search, and paths are rebuilt without any locking.
+///
+/// Entries are accessed with [`ArenaNode`] handles, which are `Copy`.
+pub struct TocArena {
+    entries: Vec<ArenaEntry>,
+    names: String,
+    name_spans: Vec<(u32, u32)>,
+}
+
+#[derive(Clone, Copy)]
+struct ArenaEntry {
+    parent: u32,
+    name: u32,
+    first_child: u32,
+    child_count: u32,
+    cache_offset: i64,
+    timestamp: i64,
+    comp_len: i32,
+    len: i32,
+}
+
+impl TocArena {
+    /// Reads the TOC file at the given path into an arena.
+    ///
+    /// Replaced entries are left out, like when reading the TOC of a
+    /// [`CachePairReader`](crate::cache_pair::CachePairReader).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOC file cannot be read, if its header is invalid, or if an entry
+    /// has an invalid name or parent directory.
+    pub fn read<P: AsRef<Path>>(toc_path: P) -> Result<Self> {
+        let (_, toc_entries) = read_toc_entries(toc_path.as_ref())?;
+
+        let mut names = String::new();
+        let mut name_spans = Vec::new();
+        let mut interned = HashMap::new();
+        let mut intern = |name: &str| -> u32 {
+            if let Some(&id) = interned.get(name) {
+                return id;
+            }
+            let id = name_spans.len() as u32;
+            name_spans.push((names.len() as u32, name.len() as u32));
+            names.push_str(name);
+            interned.insert(name.to_string(), id);
+            id
+        };
+
+        // Entries in TOC order, with the TOC directory index of their parent
+        let mut entries = vec![ArenaEntry::directory(ROOT_INDEX, intern(""))];
+        let mut children: Vec<Vec<u32>> = vec![Vec::new()];
+        let mut directory_indices = vec![ROOT_INDEX];
+
+        for (index, toc_entry) in toc_entries.iter().enumerate() {
+            // Replaced entries have a timestamp of 0
+            if toc_entry.timestamp == 0 {
+                continue;
+            }
+
+            let name = toc_entry
+                .name()
+                .map_err(|source| Error::InvalidEntryName { index, source })?;
+            let parent = usize::try_from(toc_entry.parent_dir_index)
+                .ok()
+                .and_then(|parent_index| directory_indices.get(parent_index))
+                .copied()
+                .ok_or(Error::ParentIndexOutOfRange {
+                    index,
+                    parent_index: toc_entry.parent_dir_index,
+                })?;
+
+            let entry_index = entries.len() as u32;
+            let name = intern(name);
+            if toc_entry.is_directory() {
+                entries.push(ArenaEntry::directory(parent, name));
+                directory_indices.push(entry_index);
+            } else {
+                entries.push(ArenaEntry {
+                    parent,
+                    name,
+                    first_child: 0,
+                    child_count: 0,
+                    cache_offset: toc_entry.cache_offset,
+                    timestamp: toc_entry.timestamp,
+                    comp_len: toc_entry.comp_len,
+                    len: toc_entry.len,
+                });
+            }
+            children.push(Vec::new());
+            children[parent as usize].push(entry_index);
+        }
+
+        let mut arena = Self {
+            entries: Vec::with_capacity(entries.len()),
+            names,
+            name_spans,
+        };
+        arena.names.shrink_to_fit();
+        arena.name_spans.shrink_to_fit();
+
+        // Lay out the entries breadth-first so that the children of every
+        // directory are contiguous, sorted by name
+        arena.entries.push(entries[ROOT_INDEX as usize]);
+        let mut next = 0;
+        while next < arena.entries.len() {
+            let old_index = arena.entries[next].first_child as usize;
+            let mut directory_children = std::mem::take(&mut children[old_index]);
+            // The sort is stable, so duplicated names keep the TOC order
+            directory_children.sort_by(|&a, &b| {
+                arena
+                    .name(entries[a as usize].name)
+                    .cmp(arena.name(entries[b as usize].name))
+            });
+
+            let first_child = arena.entries.len() as u32;
+            for &child in &directory_children {
+                let mut entry = entries[child as usize];
+                entry.parent = next as u32;
+                // Keep the old index until the children of the entry are laid out
+                entry.first_child = child;
+                arena.entries.push(entry);
+            }
+
+            let entry = &mut arena.entries[next];
+            entry.first_child = first_child;
+            entry.child_count = directory_children.len() as u32;
+            next += 1;
+        }
+
+        Ok(arena)
+    }
+
+    /// Returns the root directory.
+    pub fn root(&self) -> ArenaNode<'_> {
+        self.node(ROOT_INDEX)
+    }
+
+    /// Returns the number of entries, including the root directory.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the arena only holds the root directory.
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() <= 1
+    }
+
+    /// Returns the node at the given index, as returned by [`ArenaNode::index`].
+    ///
+    /// Returns `None` if the index is out of range.
+    pub fn get(&self, index: u32) -> Option<ArenaNode<'_>> {
+        ((index as usize) < self.entries.len()).then(|| self.node(index))
+    }
+
+    /// Returns the node at the given absolute path.
+    pub fn get_node<P: AsRef<Path>>(&self, path: P) -> Option<ArenaNode<'_>> {
+        let path = path.as_ref();
+        if !path.has_root() {
+            return None;
+        }
+
+        let mut node = self.root();
+        for component in path.components() {
+            node = match component {
+                Component::RootDir | Component::CurDir => node,
+                Component::ParentDir => node.parent()?,
+                Component::Normal(name) => node.get_child(name.to_str()?)?,
+                Component::Prefix(_) => return None,
+            };
+        }
+        Some(node)
+    }
+
+    /// Returns every file node, in the order of the arena.
+    pub fn files(&self) -> impl Iterator<Item = ArenaNode<'_>> {
+        (0..self.entries.len() as u32)
+            .map(|index| self.node(index))
+            .filter(|node| node.kind() == NodeKind::File)
+    }
+
+    /// Returns every directory node, in the order of the arena.
+    pub fn directories(&self) -> impl Iterator<Item = ArenaNode<'_>> {
+        (0..self.entries.len() as u32)
+            .map(|index| self.node(index))
+            .filter(|node| node.kind() == NodeKind::Directory)
+    }
+
+    /// Returns the memory used by the arena, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + self.entries.capacity() * size_of::<ArenaEntry>()
+            + self.names.capacity()
+            + self.name_spans.capacity() * size_of::<(u32, u32)>()
+    }
+
+    fn node(&self, index: u32) -> ArenaNode<'_> {
+        ArenaNode { arena: self, index }
+    }
+
+    fn name(&self, name: u32) -> &str {
+        let (start, len) = self.name_spans[name as usize];
+        &self.names[start as usize..(start + len) as usize]
+    }
+}
+
+impl ArenaEntry {
+    fn directory(parent: u32, name: u32) -> Self {
+        Self {
+            parent,
+            name,
+            first_child: 0,
+            child_count: 0,
+            cache_offset: -1,
+            timestamp: 0,
+            comp_len: 0,
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Debug for TocArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TocArena")
+            .field("entries", &self.entries.len())
+            .field("names", &self.name_spans.len())
+            .finish()
+    }
+}
+
+/// A handle to an entry of a [`TocArena`].
+///
+/// Handles are `Copy`: they only hold a reference to the arena and the index of the entry.
+#[derive(Clone, Copy)]
+pub struct ArenaNode<'a> {
+    arena: &'a TocArena,
+    index: u32,
+}
+
+impl<'a> ArenaNode<'a> {
+    /// Returns the index of the node in the arena.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the name of the node.
+    pub fn name(&self) -> &'a str {
+        self.arena.name(self.entry().name)
+    }
+
+    /// Returns the path of the node.
+    pub fn path(&self) -> PathBuf {
+        let mut path = String::new();
+        self.write_path(&mut path);
+        PathBuf::from(path)
+    }
+
+    /// Writes the path of the node to the given string, with `/` separators.
+    ///
+    /// The string is cleared first, which allows rebuilding many paths without allocating.
+    pub fn write_path(&self, path: &mut String) {
+        path.clear();
+        match self.parent() {
+            Some(parent) => parent.push_path(path, self.name()),
+            None => path.push('/'),
+        }
+    }
+
+    /// Appends the path of the node followed by the given name.
+    fn push_path(&self, path: &mut String, name: &str) {
+        if let Some(parent) = self.parent() {
+            parent.push_path(path, self.name());
+        }
+        path.push('/');
+        path.push_str(name);
+    }
+
+    /// Returns the kind of the node.
+    pub fn kind(&self) -> NodeKind {
+        match self.entry().cache_offset {
+            -1 => NodeKind::Directory,
+            _ => NodeKind::File,
+        }
+    }
+
+    /// Returns the parent of the node, or `None` for the root directory.
+    pub fn parent(&self) -> Option<ArenaNode<'a>> {
+        match self.index {
+            ROOT_INDEX => None,
+            _ => Some(self.arena.node(self.entry().parent)),
+        }
+    }
+
+    /// Returns the children of the directory, sorted by name.
+    pub fn children(&self) -> impl ExactSizeIterator<Item = ArenaNode<'a>> {
+        let arena = self.arena;
+        let entry = self.entry();
+        (entry.first_child..entry.first_child + entry.child_count).map(|index| arena.node(index))
+    }
+
+    /// Returns the child with the given name.
+    ///
+    /// When a name is duplicated, the first child in the TOC order is returned, like the path
+    /// index of a [`CachePairReader`](crate::cache_pair::CachePairReader) does.
+    pub fn get_child(&self, name: &str) -> Option<ArenaNode<'a>> {
+        let entry = self.entry();
+        let children = &self.arena.entries
+            [entry.first_child as usize..(entry.first_child + entry.child_count) as usize];
+        let position = children.partition_point(|child| self.arena.name(child.name) < name);
+        let child = children.get(position)?;
+        (self.arena.name(child.name) == name)
+            .then(|| self.arena.node(entry.first_child + position as u32))
+    }
+
+    fn entry(&self) -> &'a ArenaEntry {
+        &self.arena.entries[self.index as usize]
+    }
+}
+
+impl FileNode for ArenaNode<'_> {
+    fn cache_offset(&self) -> i64 {
+        self.entry().cache_offset
+    }
+
+    fn timestamp(&self) -> i64 {
+        self.entry().timestamp
+    }
+
+    fn comp_len(&self) -> i32 {
+        self.entry().comp_len
+    }
+
+    fn len(&self) -> i32 {
+        self.entry().len
+    }
+}
+
+impl PartialEq for ArenaNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.arena, other.arena) && self.index == other.index
+    }
+}
+
+impl Eq for ArenaNode<'_> {}
+
+impl fmt::Debug for ArenaNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entry = self.entry();
+        f.debug_struct("ArenaNode")
+            .field("index", &self.index)
+            .field("name", &self.name())
+            .field("kind", &self.kind())
+            .field("cache_offset", &entry.cache_offset)
+            .field("timestamp", &entry.timestamp)
+            .field("comp_len", &entry.comp_len)
+            .field("len", &entry.len)
+            .finish()
+    }
+}
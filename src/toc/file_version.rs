@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+
+use crate::toc::node::FileNode;
+
+/// A version of a file that has been replaced by a newer one.
+///
+/// Replaced versions are kept in the TOC file with a timestamp of 0, and their data may still be
+/// present in the cache file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileVersion {
+    path: PathBuf,
+    cache_offset: i64,
+    timestamp: i64,
+    comp_len: i32,
+    len: i32,
+}
+
+impl FileVersion {
+    pub(super) fn new(
+        path: PathBuf,
+        cache_offset: i64,
+        timestamp: i64,
+        comp_len: i32,
+        len: i32,
+    ) -> Self {
+        Self {
+            path,
+            cache_offset,
+            timestamp,
+            comp_len,
+            len,
+        }
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl FileNode for FileVersion {
+    fn cache_offset(&self) -> i64 {
+        self.cache_offset
+    }
+
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    fn comp_len(&self) -> i32 {
+        self.comp_len
+    }
+
+    fn len(&self) -> i32 {
+        self.len
+    }
+}
@@ -8,11 +8,25 @@ trait provides methods for getting information about a file, such as the cache o
 compressed length, and decompressed length. The [`DirectoryNode`] trait provides methods for getting
 information about a directory, such as its children and a child with a given name.
 
+A subtree can be iterated lazily with [`Node::walk`], which returns a [`Walk`] iterator.
+
+[`TocArena`] is a compact and immutable alternative to the tree of [`Node`]s, for large TOCs.
+
 */
 
+mod arena;
+mod file_version;
 mod node;
 mod toc;
 mod toc_entry;
+mod toc_header;
+mod toc_index;
+mod walk;
 
+pub use arena::{ArenaNode, TocArena};
+pub use file_version::FileVersion;
 pub use node::{DirectoryNode, FileNode, Node, NodeKind};
-pub(crate) use toc::Toc;
+pub use walk::{Walk, WalkOrder};
+pub(crate) use toc::{read_toc_entries, read_whole_toc_entries, write_toc_entries, Toc};
+pub(crate) use toc_entry::TocEntry;
+pub(crate) use toc_header::{ARCHIVE_VERSION, MAGIC_NUMBER};
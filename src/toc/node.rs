@@ -3,6 +3,8 @@ use std::path::PathBuf;
 
 use arctree::Node as ArcNode;
 
+use crate::toc::walk::Walk;
+
 /// The kind of a node.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum NodeKind {
@@ -37,6 +39,9 @@ impl fmt::Debug for Node {
 }
 
 /// Trait for file nodes.
+///
+/// Directory nodes have no cache data, so they return a cache offset of `-1`, as stored in the
+/// TOC file, and `0` for the other fields.
 pub trait FileNode {
     /// Returns the cache offset of the file.
     fn cache_offset(&self) -> i64;
@@ -51,6 +56,24 @@ pub trait FileNode {
     fn len(&self) -> i32;
 }
 
+impl<F: FileNode + ?Sized> FileNode for &F {
+    fn cache_offset(&self) -> i64 {
+        (**self).cache_offset()
+    }
+
+    fn timestamp(&self) -> i64 {
+        (**self).timestamp()
+    }
+
+    fn comp_len(&self) -> i32 {
+        (**self).comp_len()
+    }
+
+    fn len(&self) -> i32 {
+        (**self).len()
+    }
+}
+
 /// Trait for directory nodes.
 pub trait DirectoryNode {
     /// Returns the children of the directory.
@@ -143,23 +166,40 @@ impl Node {
     pub fn parent(&self) -> Option<Node> {
         self.node.parent().map(|parent| Node { node: parent })
     }
+
+    /// Returns an iterator over the subtree of the node, starting with the node itself.
+    ///
+    /// The iterator is depth-first by default, see [`Walk`] to configure it.
+    pub fn walk<'a>(&self) -> Walk<'a> {
+        Walk::new(self.clone())
+    }
+
+    pub(super) fn first_child(&self) -> Option<Node> {
+        self.node.first_child().map(|child| Node { node: child })
+    }
+
+    pub(super) fn next_sibling(&self) -> Option<Node> {
+        self.node
+            .next_sibling()
+            .map(|sibling| Node { node: sibling })
+    }
 }
 
 impl FileNode for Node {
     fn cache_offset(&self) -> i64 {
-        self.node.read().cache_offset().unwrap().clone()
+        self.node.read().cache_offset().copied().unwrap_or(-1)
     }
 
     fn timestamp(&self) -> i64 {
-        self.node.read().timestamp().unwrap().clone()
+        self.node.read().timestamp().copied().unwrap_or(0)
     }
 
     fn comp_len(&self) -> i32 {
-        self.node.read().comp_len().unwrap().clone()
+        self.node.read().comp_len().copied().unwrap_or(0)
     }
 
     fn len(&self) -> i32 {
-        self.node.read().len().unwrap().clone()
+        self.node.read().len().copied().unwrap_or(0)
     }
 }
 
@@ -1,28 +1,52 @@
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::{Component, PathBuf};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
 
-use anyhow::Result;
-use zerocopy::FromBytes;
+use log::warn;
+use zerocopy::{AsBytes, FromZeroes};
 
+use crate::toc::file_version::FileVersion;
 use crate::toc::node::{DirectoryNode, Node, NodeKind};
 use crate::toc::toc_entry::{TocEntry, TOC_ENTRY_SIZE};
+use crate::toc::toc_header::{TocHeader, TOC_HEADER_SIZE};
+use crate::toc::toc_index::{read_index, write_index, IndexKey};
+use crate::{Error, Result};
+
+/// The approximate memory used by a node besides its name and path, that is the
+/// `arctree` node with its links, lock and reference counts, the node data, and
+/// its slots in the node vectors and in the path index.
+const NODE_OVERHEAD: usize = 160;
 
 pub(crate) struct Toc {
     toc_path: PathBuf,
+    version: Option<u32>,
+    keep_history: bool,
     directories: Vec<Node>,
     files: Vec<Node>,
+    paths: HashMap<PathBuf, Node>,
+    replaced_files: HashMap<PathBuf, Vec<FileVersion>>,
+    memory_usage: usize,
 }
 
 impl Toc {
     pub fn new(toc_path: PathBuf) -> Self {
         Self {
             toc_path,
+            version: None,
+            keep_history: false,
             directories: Vec::new(),
             files: Vec::new(),
+            paths: HashMap::new(),
+            replaced_files: HashMap::new(),
+            memory_usage: 0,
         }
     }
 
+    pub fn version(&self) -> Option<u32> {
+        self.version
+    }
+
     pub fn directories(&self) -> &Vec<Node> {
         &self.directories
     }
@@ -31,10 +55,33 @@ impl Toc {
         &self.files
     }
 
+    pub fn keeps_history(&self) -> bool {
+        self.keep_history
+    }
+
+    pub fn set_keep_history(&mut self, keep_history: bool) {
+        self.keep_history = keep_history;
+    }
+
+    pub fn replaced_versions(&self, path: &Path) -> &[FileVersion] {
+        self.replaced_files
+            .get(path)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn replaced_files(&self) -> impl Iterator<Item = &FileVersion> {
+        self.replaced_files.values().flatten()
+    }
+
     pub fn root(&self) -> Option<Node> {
         self.directories.get(0).cloned()
     }
 
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
     pub fn is_loaded(&self) -> bool {
         !self.directories.is_empty()
     }
@@ -48,53 +95,119 @@ impl Toc {
         // from a previous read
         self.unread_toc();
 
-        let mut toc_reader = File::open(&self.toc_path).unwrap();
-        let entry_count = (toc_reader.metadata().unwrap().len() as usize - 8) / TOC_ENTRY_SIZE;
-        toc_reader.seek(SeekFrom::Start(8)).unwrap();
+        // Do not leave a partially read TOC behind on failure
+        let result = read_toc_entries(&self.toc_path)
+            .and_then(|(header, entries)| self.load_entries(header.archive_version, &entries));
+        if result.is_err() {
+            self.unread_toc();
+        }
+        result
+    }
+
+    /// Reads the TOC from the given index file if it is still valid, or from
+    /// the TOC file otherwise, in which case the index file is rewritten.
+    ///
+    /// Returns whether the TOC was loaded from the index file.
+    pub fn read_toc_with_index(&mut self, index_path: &Path) -> Result<bool> {
+        if self.is_loaded() {
+            return Ok(false); // TOC already loaded
+        }
+
+        self.unread_toc();
+
+        // The key is taken before reading the TOC file, so that an index
+        // written while the TOC file changes is stale on the next read
+        let key = IndexKey::of(&self.toc_path)?;
+        if let Some((version, entries)) = read_index(index_path, &key, self.keep_history) {
+            if self.load_entries(version, &entries).is_ok() {
+                return Ok(true);
+            }
+            self.unread_toc();
+        }
+
+        let (header, entries) = read_toc_entries(&self.toc_path)?;
+        if let Err(error) = self.load_entries(header.archive_version, &entries) {
+            self.unread_toc();
+            return Err(error);
+        }
+
+        // The TOC is still usable without its index
+        if let Err(error) = write_index(
+            index_path,
+            &key,
+            header.archive_version,
+            &entries,
+            self.keep_history,
+        ) {
+            warn!(
+                "Failed to write TOC index {}: {}",
+                index_path.display(),
+                error
+            );
+        }
+
+        Ok(false)
+    }
 
+    fn load_entries(&mut self, archive_version: u32, entries: &[TocEntry]) -> Result<()> {
         // Reserve space for the entries in the vectors to avoid unnecessary
         // reallocations
-        self.files.reserve(entry_count);
-        self.directories.reserve(entry_count);
+        self.files.reserve(entries.len());
+        self.directories.reserve(entries.len());
+        self.paths.reserve(entries.len() + 1);
 
         let mut file_count = 0;
         let mut dir_count = 1; // Hardcoded root directory
 
-        self.directories.insert(0, Node::root());
+        // Full paths of the directories, indexed like `self.directories`, so
+        // that the path of each entry is built from its parent in one join
+        let mut directory_paths = vec![PathBuf::from("/")];
 
-        let mut buffer = vec![0u8; TOC_ENTRY_SIZE * entry_count];
-        toc_reader.read_exact(&mut buffer).unwrap();
+        let root = Node::root();
+        self.paths.insert(PathBuf::from("/"), root.clone());
+        self.directories.insert(0, root);
 
-        let entries = TocEntry::slice_from(&buffer).unwrap();
-        for entry in entries {
+        for (index, entry) in entries.iter().enumerate() {
             // Entry timestamp of 0 means the entry has been replaced with a
             // newer version with the same name and path with a valid timestamp
             if entry.timestamp == 0 {
+                if self.keep_history && !entry.is_directory() {
+                    self.read_replaced_file(index, entry, &directory_paths)?;
+                }
                 continue;
             }
 
-            // Entry name is a null-terminated string, so we need to find the
-            // index of the null byte and truncate the string there
-            let entry_name = {
-                let null_index = entry.name.iter().position(|&x| x == 0).unwrap_or(64);
-                let entry_name = std::str::from_utf8(&entry.name[..null_index])?;
-                entry_name
-            };
-
-            let parent_node = self
-                .directories
-                .get_mut(entry.parent_dir_index as usize)
-                .unwrap();
-
-            // If the cache offset is -1, then the entry is a directory
-            if entry.cache_offset == -1 {
+            // Entry name is a null-terminated string, so it is truncated at
+            // the first null byte
+            let entry_name = entry
+                .name()
+                .map_err(|source| Error::InvalidEntryName { index, source })?;
+
+            let parent_index = usize::try_from(entry.parent_dir_index)
+                .ok()
+                .filter(|&parent_index| parent_index < self.directories.len())
+                .ok_or(Error::ParentIndexOutOfRange {
+                    index,
+                    parent_index: entry.parent_dir_index,
+                })?;
+            let parent_node = &mut self.directories[parent_index];
+            let path = directory_paths[parent_index].join(entry_name);
+            self.memory_usage += NODE_OVERHEAD + entry_name.len() + path.as_os_str().len();
+
+            if entry.is_directory() {
                 let dir_node = Node::directory(entry_name);
 
                 parent_node.append(dir_node.clone());
-                self.directories.insert(dir_count, dir_node);
+                self.directories.insert(dir_count, dir_node.clone());
+                directory_paths.push(path.clone());
+
+                // Keep the first node when a path is duplicated, as the
+                // component walk would
+                self.paths.entry(path).or_insert(dir_node);
 
                 dir_count += 1;
             } else {
+                validate_lengths(index, entry)?;
                 let file_node = Node::file(
                     entry_name,
                     entry.cache_offset,
@@ -104,7 +217,8 @@ impl Toc {
                 );
 
                 parent_node.append(file_node.clone());
-                self.files.insert(file_count, file_node);
+                self.files.insert(file_count, file_node.clone());
+                self.paths.entry(path).or_insert(file_node);
 
                 file_count += 1;
             }
@@ -113,13 +227,53 @@ impl Toc {
         // Shrink the vectors to the actual size of the vectors to save memory
         self.directories.shrink_to_fit();
         self.files.shrink_to_fit();
+        self.paths.shrink_to_fit();
+
+        self.version = Some(archive_version);
 
         Ok(()) // TOC read successfully
     }
 
+    fn read_replaced_file(
+        &mut self,
+        index: usize,
+        entry: &TocEntry,
+        directory_paths: &[PathBuf],
+    ) -> Result<()> {
+        let entry_name = entry
+            .name()
+            .map_err(|source| Error::InvalidEntryName { index, source })?;
+
+        let parent_path = usize::try_from(entry.parent_dir_index)
+            .ok()
+            .and_then(|parent_index| directory_paths.get(parent_index))
+            .ok_or(Error::ParentIndexOutOfRange {
+                index,
+                parent_index: entry.parent_dir_index,
+            })?;
+
+        validate_lengths(index, entry)?;
+        let path = parent_path.join(entry_name);
+        self.memory_usage += size_of::<FileVersion>() + 2 * path.as_os_str().len();
+        let version = FileVersion::new(
+            path.clone(),
+            entry.cache_offset,
+            entry.timestamp,
+            entry.comp_len,
+            entry.len,
+        );
+        self.replaced_files.entry(path).or_default().push(version);
+
+        Ok(())
+    }
+
     pub fn unread_toc(&mut self) {
+        self.version = None;
         self.directories.clear();
         self.files.clear();
+        self.paths.clear();
+        self.replaced_files.clear();
+        self.memory_usage = 0;
     }
 
     fn get_node(&self, path: PathBuf) -> Option<Node> {
@@ -127,12 +281,20 @@ impl Toc {
             return None;
         }
 
+        // Only absolute paths can be resolved from the root
         if !path.has_root() {
-            panic!("Path must be absolute");
+            return None;
+        }
+
+        // Paths are compared component-wise, so redundant separators and `.`
+        // components are already ignored by the index. Only `..` components
+        // need to be resolved by walking the tree.
+        if !path.components().any(|c| c == Component::ParentDir) {
+            return self.paths.get(&path).cloned();
         }
 
         let mut components = path.components();
-        let mut current_node = self.root().unwrap().clone();
+        let mut current_node = self.root()?;
 
         // Skip root
         components.next();
@@ -140,7 +302,7 @@ impl Toc {
         for component in components {
             match component {
                 Component::Normal(name) => {
-                    let name = name.to_str().unwrap();
+                    let name = name.to_str()?;
                     current_node = match current_node.get_child(name) {
                         Some(child) => child,
                         _ => return None,
@@ -180,3 +342,57 @@ impl Toc {
         }
     }
 }
+
+/// Checks that the compressed and decompressed lengths of a file entry are not
+/// negative.
+fn validate_lengths(index: usize, entry: &TocEntry) -> Result<()> {
+    match [entry.comp_len, entry.len].into_iter().find(|&len| len < 0) {
+        Some(len) => Err(Error::InvalidEntryLength {
+            index: Some(index),
+            len,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Reads and validates the header of the given TOC file, and returns it along
+/// with all of its entries, including the replaced ones.
+pub(crate) fn read_toc_entries(toc_path: &Path) -> Result<(TocHeader, Vec<TocEntry>)> {
+    let (header, entries, trailing_len) = read_whole_toc_entries(toc_path)?;
+    if trailing_len != 0 {
+        return Err(Error::TruncatedTocEntry {
+            index: entries.len(),
+        });
+    }
+
+    Ok((header, entries))
+}
+
+/// Reads and validates the header of the given TOC file, and returns it along
+/// with all of its whole entries and the length of the truncated entry at the
+/// end of the file, if any.
+pub(crate) fn read_whole_toc_entries(toc_path: &Path) -> Result<(TocHeader, Vec<TocEntry>, usize)> {
+    let mut toc_reader = File::open(toc_path)?;
+    let toc_len = toc_reader.metadata()?.len() as usize;
+
+    let mut header = TocHeader::new_zeroed();
+    toc_reader.read_exact(header.as_bytes_mut())?;
+    header.validate()?;
+
+    let entries_len = toc_len - TOC_HEADER_SIZE;
+    let entry_count = entries_len / TOC_ENTRY_SIZE;
+
+    let mut entries = vec![TocEntry::new_zeroed(); entry_count];
+    toc_reader.read_exact(entries.as_bytes_mut())?;
+
+    Ok((header, entries, entries_len % TOC_ENTRY_SIZE))
+}
+
+/// Writes the TOC header followed by the given entries to the TOC file.
+pub(crate) fn write_toc_entries(toc_path: &Path, entries: &[TocEntry]) -> Result<()> {
+    let mut toc_writer = File::create(toc_path)?;
+    toc_writer.write_all(TocHeader::new().as_bytes())?;
+    toc_writer.write_all(entries.as_bytes())?;
+    toc_writer.flush()?;
+    Ok(())
+}
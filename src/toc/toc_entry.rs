@@ -1,10 +1,11 @@
 use std::mem;
+use std::str::Utf8Error;
 
-use zerocopy::{FromBytes, FromZeroes};
+use zerocopy::{AsBytes, FromBytes, FromZeroes};
 
 /// This const is the size of a single TOC entry in bytes. It is used to
 /// determine the size of the buffer to read from the TOC file.
-pub(super) const TOC_ENTRY_SIZE: usize = mem::size_of::<TocEntry>();
+pub(crate) const TOC_ENTRY_SIZE: usize = mem::size_of::<TocEntry>();
 
 /// This struct represents a single entry in the TOC file. It contains the
 /// offset in the cache file where the file is located and other metadata.
@@ -13,8 +14,8 @@ pub(super) const TOC_ENTRY_SIZE: usize = mem::size_of::<TocEntry>();
 /// file or directory. The name is a null-terminated string, so the name
 /// should be truncated at the first null byte.
 #[repr(C)]
-#[derive(FromBytes, FromZeroes)]
-pub(super) struct TocEntry {
+#[derive(AsBytes, FromBytes, FromZeroes, Clone)]
+pub(crate) struct TocEntry {
     pub cache_offset: i64,
     pub timestamp: i64,
     pub comp_len: i32,
@@ -23,3 +24,46 @@ pub(super) struct TocEntry {
     pub parent_dir_index: i32,
     pub name: [u8; 64],
 }
+
+impl TocEntry {
+    /// Creates a new entry, truncating the name to 64 bytes.
+    pub fn new(
+        name: &str,
+        cache_offset: i64,
+        timestamp: i64,
+        comp_len: i32,
+        len: i32,
+        parent_dir_index: i32,
+    ) -> Self {
+        let mut entry = Self {
+            cache_offset,
+            timestamp,
+            comp_len,
+            len,
+            reserved: 0,
+            parent_dir_index,
+            name: [0u8; 64],
+        };
+
+        let name_len = name.len().min(entry.name.len());
+        entry.name[..name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+        entry
+    }
+
+    /// Returns the name of the entry, truncated at the first null byte.
+    pub fn name(&self) -> Result<&str, Utf8Error> {
+        let null_index = self
+            .name
+            .iter()
+            .position(|&x| x == 0)
+            .unwrap_or(self.name.len());
+        std::str::from_utf8(&self.name[..null_index])
+    }
+
+    /// Returns whether the entry is a directory.
+    ///
+    /// Directories have a cache offset of -1 as they have no data.
+    pub fn is_directory(&self) -> bool {
+        self.cache_offset == -1
+    }
+}
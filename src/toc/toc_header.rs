@@ -0,0 +1,50 @@
+use std::mem;
+
+use zerocopy::{AsBytes, FromBytes, FromZeroes};
+
+use crate::{Error, Result};
+
+/// This const is the size of the TOC header in bytes. The TOC entries start
+/// right after the header.
+pub(crate) const TOC_HEADER_SIZE: usize = mem::size_of::<TocHeader>();
+
+/// The magic number every TOC file starts with.
+pub(crate) const MAGIC_NUMBER: u32 = 0x1867C64E;
+
+/// The only archive version currently supported.
+pub(crate) const ARCHIVE_VERSION: u32 = 20;
+
+/// This struct represents the header at the start of a TOC file. It contains
+/// a magic number identifying the file as a TOC file and the version of the
+/// archive format.
+#[repr(C)]
+#[derive(AsBytes, FromBytes, FromZeroes)]
+pub(crate) struct TocHeader {
+    pub magic_number: u32,
+    pub archive_version: u32,
+}
+
+impl TocHeader {
+    /// Creates a header with the magic number and the supported archive
+    /// version.
+    pub fn new() -> Self {
+        Self {
+            magic_number: MAGIC_NUMBER,
+            archive_version: ARCHIVE_VERSION,
+        }
+    }
+
+    /// Checks that the header has the expected magic number and a supported
+    /// archive version.
+    pub fn validate(&self) -> Result<()> {
+        if self.magic_number != MAGIC_NUMBER {
+            return Err(Error::InvalidMagic(self.magic_number));
+        }
+
+        if self.archive_version != ARCHIVE_VERSION {
+            return Err(Error::UnsupportedVersion(self.archive_version));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use zerocopy::FromZeroes;
+
+use crate::toc::toc_entry::TocEntry;
+use crate::Result;
+
+/// The magic number at the start of an index file.
+const INDEX_MAGIC: [u8; 4] = *b"LTIX";
+
+/// The version of the index file format, bumped on any layout change so that
+/// older index files are considered stale.
+const INDEX_VERSION: u32 = 1;
+
+/// The size of an index record in bytes, besides its name.
+const INDEX_RECORD_SIZE: usize = 29;
+
+/// Set in the header flags when the replaced entries are part of the index.
+const FLAG_HISTORY: u32 = 1;
+
+/// Identifies the state of the TOC file an index was built from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IndexKey {
+    toc_len: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+impl IndexKey {
+    /// Returns the key of the TOC file in its current state.
+    pub fn of(toc_path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(toc_path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            toc_len: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        })
+    }
+}
+
+/// Reads the archive version and the entries stored in the index file.
+///
+/// Returns `None` if the index file does not exist, is invalid, was built from
+/// another state of the TOC file, or lacks the replaced entries while they are
+/// needed.
+pub(crate) fn read_index(
+    index_path: &Path,
+    key: &IndexKey,
+    needs_history: bool,
+) -> Option<(u32, Vec<TocEntry>)> {
+    let data = fs::read(index_path).ok()?;
+    let mut reader = data.as_slice();
+
+    if take::<4>(&mut reader)? != INDEX_MAGIC || read_u32(&mut reader)? != INDEX_VERSION {
+        return None;
+    }
+
+    let index_key = IndexKey {
+        toc_len: read_u64(&mut reader)?,
+        mtime_secs: read_u64(&mut reader)?,
+        mtime_nanos: read_u32(&mut reader)?,
+    };
+    if index_key != *key {
+        return None;
+    }
+
+    let archive_version = read_u32(&mut reader)?;
+    let flags = read_u32(&mut reader)?;
+    if needs_history && flags & FLAG_HISTORY == 0 {
+        return None;
+    }
+
+    // Bound the allocation by the size of the file in case the count is corrupt
+    let entry_count = usize::try_from(read_u64(&mut reader)?).ok()?;
+    let mut entries = Vec::with_capacity(entry_count.min(reader.len() / INDEX_RECORD_SIZE));
+    for _ in 0..entry_count {
+        let mut entry = TocEntry::new_zeroed();
+        entry.cache_offset = read_u64(&mut reader)? as i64;
+        entry.timestamp = read_u64(&mut reader)? as i64;
+        entry.comp_len = read_u32(&mut reader)? as i32;
+        entry.len = read_u32(&mut reader)? as i32;
+        entry.parent_dir_index = read_u32(&mut reader)? as i32;
+
+        let name_len = take::<1>(&mut reader)?[0] as usize;
+        if name_len > entry.name.len() || name_len > reader.len() {
+            return None;
+        }
+        let (name, rest) = reader.split_at(name_len);
+        entry.name[..name_len].copy_from_slice(name);
+        reader = rest;
+
+        entries.push(entry);
+    }
+
+    reader.is_empty().then_some((archive_version, entries))
+}
+
+/// Writes the entries of the TOC file to the index file.
+///
+/// Replaced entries are only written if `keep_history` is set, and replaced
+/// directories never are, as they are not part of the parsed tree. The index
+/// file is written next to its final path first and then renamed, so that a
+/// partially written index is never read.
+pub(crate) fn write_index(
+    index_path: &Path,
+    key: &IndexKey,
+    archive_version: u32,
+    entries: &[TocEntry],
+    keep_history: bool,
+) -> Result<()> {
+    let entries: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.timestamp != 0 || (keep_history && !entry.is_directory()))
+        .collect();
+    let flags = if keep_history { FLAG_HISTORY } else { 0 };
+
+    let mut temp_path = index_path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    writer.write_all(&INDEX_MAGIC)?;
+    writer.write_all(&INDEX_VERSION.to_le_bytes())?;
+    writer.write_all(&key.toc_len.to_le_bytes())?;
+    writer.write_all(&key.mtime_secs.to_le_bytes())?;
+    writer.write_all(&key.mtime_nanos.to_le_bytes())?;
+    writer.write_all(&archive_version.to_le_bytes())?;
+    writer.write_all(&flags.to_le_bytes())?;
+    writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+    for entry in entries {
+        // Names are null-terminated, only the bytes before the first null are
+        // stored
+        let name_len = entry
+            .name
+            .iter()
+            .position(|&x| x == 0)
+            .unwrap_or(entry.name.len());
+
+        writer.write_all(&entry.cache_offset.to_le_bytes())?;
+        writer.write_all(&entry.timestamp.to_le_bytes())?;
+        writer.write_all(&entry.comp_len.to_le_bytes())?;
+        writer.write_all(&entry.len.to_le_bytes())?;
+        writer.write_all(&entry.parent_dir_index.to_le_bytes())?;
+        writer.write_all(&[name_len as u8])?;
+        writer.write_all(&entry.name[..name_len])?;
+    }
+
+    writer.into_inner().map_err(io::Error::from)?.sync_all()?;
+    fs::rename(&temp_path, index_path)?;
+
+    Ok(())
+}
+
+fn take<const N: usize>(reader: &mut &[u8]) -> Option<[u8; N]> {
+    if reader.len() < N {
+        return None;
+    }
+    let (bytes, rest) = reader.split_at(N);
+    *reader = rest;
+    bytes.try_into().ok()
+}
+
+fn read_u32(reader: &mut &[u8]) -> Option<u32> {
+    take(reader).map(u32::from_le_bytes)
+}
+
+fn read_u64(reader: &mut &[u8]) -> Option<u64> {
+    take(reader).map(u64::from_le_bytes)
+}
@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::toc::node::{Node, NodeKind};
+
+/// The order in which a [`Walk`] visits the nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WalkOrder {
+    /// Visit every descendant of a directory before its next sibling.
+    #[default]
+    DepthFirst,
+
+    /// Visit every node of a depth before the nodes of the next depth.
+    BreadthFirst,
+}
+
+/// A lazy iterator over a subtree, created by [`Node::walk`].
+///
+/// The iterator yields the depth of each node along with the node, the node the walk started
+/// from being at depth `0`. Siblings are visited in the order they appear in the TOC.
+///
+/// Only the nodes left to visit are kept, as cursors to their first child or next sibling, so the
+/// children of a directory are never collected.
+pub struct Walk<'a> {
+    order: WalkOrder,
+    max_depth: Option<usize>,
+    prune: Option<PrunePredicate<'a>>,
+    pending: VecDeque<Pending>,
+}
+
+type PrunePredicate<'a> = Box<dyn FnMut(&Node) -> bool + 'a>;
+
+/// A node left to visit.
+struct Pending {
+    depth: usize,
+    node: Node,
+    /// Whether the next sibling of the node is visited after it. This is
+    /// false for the node the walk started from.
+    has_siblings: bool,
+}
+
+impl<'a> Walk<'a> {
+    pub(super) fn new(node: Node) -> Self {
+        Self {
+            order: WalkOrder::default(),
+            max_depth: None,
+            prune: None,
+            pending: VecDeque::from([Pending {
+                depth: 0,
+                node,
+                has_siblings: false,
+            }]),
+        }
+    }
+
+    /// Set the order in which the nodes are visited.
+    pub fn order(mut self, order: WalkOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set the maximum depth of the nodes yielded.
+    ///
+    /// With a maximum depth of `0`, only the node the walk started from is yielded.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set a predicate deciding which directories are pruned.
+    ///
+    /// Directories for which the predicate returns `true` are still yielded, but their children
+    /// are not visited.
+    pub fn prune<P: FnMut(&Node) -> bool + 'a>(mut self, predicate: P) -> Self {
+        self.prune = Some(Box::new(predicate));
+        self
+    }
+
+    /// Keep only the file nodes.
+    pub fn files(self) -> impl Iterator<Item = (usize, Node)> + 'a {
+        self.filter(|(_, node)| node.kind() == NodeKind::File)
+    }
+
+    fn descends_into(&mut self, depth: usize, node: &Node) -> bool {
+        if node.kind() != NodeKind::Directory {
+            return false;
+        }
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return false;
+        }
+        match self.prune.as_mut() {
+            Some(prune) => !prune(node),
+            None => true,
+        }
+    }
+}
+
+impl Iterator for Walk<'_> {
+    type Item = (usize, Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Pending {
+            depth,
+            node,
+            has_siblings,
+        } = self.pending.pop_front()?;
+
+        let sibling = match has_siblings {
+            true => node.next_sibling(),
+            false => None,
+        };
+        let child = match self.descends_into(depth, &node) {
+            true => node.first_child(),
+            false => None,
+        };
+
+        // Depth-first visits the subtree of the node before its sibling,
+        // breadth-first visits the siblings before any child.
+        if let Some(sibling) = sibling {
+            self.pending.push_front(Pending {
+                depth,
+                node: sibling,
+                has_siblings: true,
+            });
+        }
+        if let Some(child) = child {
+            let child = Pending {
+                depth: depth + 1,
+                node: child,
+                has_siblings: true,
+            };
+            match self.order {
+                WalkOrder::DepthFirst => self.pending.push_front(child),
+                WalkOrder::BreadthFirst => self.pending.push_back(child),
+            }
+        }
+
+        Some((depth, node))
+    }
+}
+
+impl fmt::Debug for Walk<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Walk")
+            .field("order", &self.order)
+            .field("max_depth", &self.max_depth)
+            .field("pruned", &self.prune.is_some())
+            .finish_non_exhaustive()
+    }
+}
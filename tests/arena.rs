@@ -0,0 +1,112 @@
+//! Tests looking up the nodes of a [`TocArena`] against the TOC of a [`CachePairReader`].
+
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::{DirectoryNode, FileNode, NodeKind, TocArena};
+use tempfile::TempDir;
+
+/// Returns the name field of a TOC entry holding the given name.
+fn name_field(name: &str) -> [u8; 64] {
+    let mut field = [0; 64];
+    field[..name.len()].copy_from_slice(name.as_bytes());
+    field
+}
+
+/// Renames the entry with the given name in the TOC file.
+fn rename_entry(toc_path: &Path, name: &str, new_name: &str) {
+    let toc = fs::read(toc_path).unwrap();
+    let offset = (8..toc.len())
+        .step_by(96)
+        .find(|offset| toc[offset + 32..offset + 96] == name_field(name))
+        .unwrap();
+
+    let mut file = OpenOptions::new().write(true).open(toc_path).unwrap();
+    file.seek(SeekFrom::Start(offset as u64 + 32)).unwrap();
+    file.write_all(&name_field(new_name)).unwrap();
+}
+
+#[test]
+fn lookups_match_toc() {
+    let dir = TempDir::new().unwrap();
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    for (path, data) in [
+        ("/Lotus/A.txt", "first"),
+        ("/Lotus/A2.txt", "second"),
+        ("/Lotus/A3.txt", "third"),
+        ("/Lotus/Sub/C.txt", "c"),
+        ("/Lotus/Other/D.txt", "d"),
+        ("/Lotus/Z.txt", "z"),
+    ] {
+        writer
+            .add_file(path, data.as_bytes(), Codec::Stored)
+            .unwrap();
+    }
+    writer.write_toc().unwrap();
+
+    // Duplicate the names of files and directories
+    rename_entry(&toc_path, "A2.txt", "A.txt");
+    rename_entry(&toc_path, "A3.txt", "A.txt");
+    rename_entry(&toc_path, "Other", "Sub");
+
+    let mut reader = CachePairReader::new(toc_path.clone(), cache_path, None);
+    reader.read_toc().unwrap();
+    let arena = TocArena::read(&toc_path).unwrap();
+
+    // Children with the same name keep the TOC order
+    let lotus = arena.get_node("/Lotus").unwrap();
+    let names: Vec<_> = lotus.children().map(|child| child.name()).collect();
+    assert_eq!(names, ["A.txt", "A.txt", "A.txt", "Sub", "Sub", "Z.txt"]);
+
+    for path in [
+        "/",
+        "/Lotus",
+        "/Lotus/A.txt",
+        "/Lotus/Sub",
+        "/Lotus/Sub/C.txt",
+        "/Lotus/Sub/../A.txt",
+        "/Lotus/Z.txt",
+        "/Lotus/B.txt",
+        "/Missing",
+    ] {
+        let node = arena.get_node(path);
+        match node.map(|node| node.kind()) {
+            Some(NodeKind::File) => {
+                let node = node.unwrap();
+                let toc_node = reader.get_file_node(path).unwrap();
+                assert_eq!(node.cache_offset(), toc_node.cache_offset(), "{path}");
+                assert_eq!(node.len(), toc_node.len(), "{path}");
+            }
+            Some(NodeKind::Directory) => {
+                let node = node.unwrap();
+                let toc_node = reader.get_directory_node(path).unwrap();
+                let names: Vec<_> = node.children().map(|child| child.name()).collect();
+                let mut toc_names: Vec<_> = toc_node
+                    .children()
+                    .into_iter()
+                    .map(|child| child.name())
+                    .collect();
+                toc_names.sort();
+                assert_eq!(names, toc_names, "{path}");
+            }
+            None => {
+                assert!(reader.get_file_node(path).is_none(), "{path}");
+                assert!(reader.get_directory_node(path).is_none(), "{path}");
+            }
+        }
+    }
+
+    // The first of the duplicated files is found
+    let node = arena.get_node("/Lotus/A.txt").unwrap();
+    let data = reader
+        .decompress_data(reader.get_file_node("/Lotus/A.txt").unwrap())
+        .unwrap();
+    assert_eq!(data, b"first");
+    assert_eq!(node.index(), lotus.children().next().unwrap().index());
+}
@@ -0,0 +1,50 @@
+//! Tests listing the blocks of cache entries with [`CachePairReader::blocks`].
+
+use lotus_lib::cache_pair::{CachePair, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::FileNode;
+use tempfile::TempDir;
+
+use common::{noise, paths, pattern, read_back};
+
+mod common;
+
+#[test]
+fn block_layout() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/Blocks.bin", &pattern(0x40000 * 2 + 5), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Stored.bin", &noise(100, 1), Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, Some(true));
+    let node = reader.get_file_node("/Lotus/Blocks.bin").unwrap();
+    let blocks = reader.blocks(&node).unwrap();
+    let decomp_lens: Vec<_> = blocks.iter().map(|block| block.decomp_len).collect();
+    assert_eq!(decomp_lens, [0x40000, 0x40000, 5]);
+    assert!(blocks.iter().all(|block| block.codec == Codec::Lz4));
+
+    // Each block is preceded by its header
+    let mut header_offset = node.cache_offset() as u64;
+    for block in &blocks {
+        assert_eq!(block.offset, header_offset + 8);
+        header_offset = block.offset + block.comp_len as u64;
+    }
+    assert_eq!(
+        header_offset,
+        (node.cache_offset() + node.comp_len() as i64) as u64
+    );
+
+    let node = reader.get_file_node("/Lotus/Stored.bin").unwrap();
+    let blocks = reader.blocks(&node).unwrap();
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].offset, node.cache_offset() as u64);
+    assert_eq!(blocks[0].comp_len, 100);
+    assert_eq!(blocks[0].codec, Codec::Stored);
+}
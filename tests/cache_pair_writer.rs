@@ -0,0 +1,216 @@
+//! Round-trip tests writing cache pairs with [`CachePairWriter`] and reading them back with
+//! [`CachePairReader`].
+
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::{DirectoryNode, FileNode, NodeKind};
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+use common::{noise, paths, pattern, read_back};
+
+mod common;
+
+fn round_trip(is_post_ensmallening: bool) {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let files = [
+        ("/Lotus/Empty.txt", Vec::new(), Codec::Lz4),
+        ("/Lotus/Stored.bin", noise(1000, 1), Codec::Stored),
+        ("/Lotus/Small.txt", pattern(100), Codec::Lz4),
+        (
+            "/Lotus/Sounds/Large.wav",
+            pattern(0x40000 * 3 + 17),
+            Codec::Lz4,
+        ),
+        ("/Lotus/Sounds/Noise.wav", noise(0x40000 + 5, 2), Codec::Lz4),
+    ];
+
+    let mut writer = CachePairWriter::new(
+        toc_path.clone(),
+        cache_path.clone(),
+        Some(is_post_ensmallening),
+    );
+    writer.add_directory("/Lotus/Empty").unwrap();
+    for (path, data, codec) in &files {
+        writer.add_file(*path, data, *codec).unwrap();
+    }
+    writer.write_toc().unwrap();
+
+    // The format is detected from the compressed entries
+    let reader = read_back(&toc_path, &cache_path, None);
+    assert_eq!(reader.is_post_ensmallening(), is_post_ensmallening);
+    assert_eq!(reader.version(), Some(20));
+    assert_eq!(reader.files().len(), files.len());
+    assert_eq!(reader.directories().len(), 4);
+
+    let empty_dir = reader.get_directory_node("/Lotus/Empty").unwrap();
+    assert_eq!(empty_dir.kind(), NodeKind::Directory);
+    assert!(empty_dir.children().is_empty());
+
+    for (path, data, _) in &files {
+        let node = reader.get_file_node(*path).unwrap();
+        assert_eq!(node.path(), Path::new(path));
+        assert_eq!(node.len() as usize, data.len());
+        assert_eq!(&reader.decompress_data(node).unwrap(), data, "{}", path);
+    }
+
+    let large = reader.get_file_node("/Lotus/Sounds/Large.wav").unwrap();
+    assert!((large.comp_len() as usize) < large.len() as usize);
+}
+
+#[test]
+fn round_trip_post_ensmallening() {
+    round_trip(true);
+}
+
+#[test]
+fn round_trip_pre_ensmallening() {
+    round_trip(false);
+}
+
+#[test]
+fn round_trip_oodle_magic_ambiguity() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    // Incompressible data starting with the Oodle magic, whose LZ4 prepended
+    // size also starts with the Oodle magic
+    let mut data = noise(0x40000 * 2 + 0x8C, 3);
+    data[0] = 0x8C;
+    data[0x40000] = 0x8C;
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/Magic.bin", &data, Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, Some(true));
+    let node = reader.get_file_node("/Lotus/Magic.bin").unwrap();
+    assert_eq!(reader.decompress_data(node).unwrap(), data);
+}
+
+#[test]
+fn append_and_replace() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/A.txt", &pattern(500), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/B.txt", &pattern(600), Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer.read_toc().unwrap();
+    writer
+        .add_file("/Lotus/B.txt", b"replaced", Codec::Stored)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Sub/C.txt", &pattern(700), Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, Some(true));
+    assert_eq!(reader.files().len(), 3);
+
+    let a = reader.get_file_node("/Lotus/A.txt").unwrap();
+    assert_eq!(reader.decompress_data(a).unwrap(), pattern(500));
+
+    let b = reader.get_file_node("/Lotus/B.txt").unwrap();
+    assert_eq!(reader.decompress_data(b).unwrap(), b"replaced");
+
+    let c = reader.get_file_node("/Lotus/Sub/C.txt").unwrap();
+    assert_eq!(reader.decompress_data(c).unwrap(), pattern(700));
+}
+
+#[test]
+fn append_with_duplicated_directory() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/A.txt", b"a", Codec::Stored)
+        .unwrap();
+    writer
+        .add_file("/Other/B.txt", b"b", Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    // Rename `/Other` to `/Lotus`, which is then listed twice
+    let mut toc = std::fs::read(&toc_path).unwrap();
+    let name_offset = 8 + 2 * 96 + 32;
+    assert_eq!(&toc[name_offset..name_offset + 6], b"Other\0");
+    toc[name_offset..name_offset + 5].copy_from_slice(b"Lotus");
+    std::fs::write(&toc_path, toc).unwrap();
+
+    // The new directory does not reuse the index of the duplicated one
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer.read_toc().unwrap();
+    writer.add_file("/New/C.txt", b"c", Codec::Stored).unwrap();
+    writer
+        .add_file("/Lotus/D.txt", b"d", Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, Some(true));
+    let c = reader.get_file_node("/New/C.txt").unwrap();
+    assert_eq!(reader.decompress_data(c).unwrap(), b"c");
+    assert!(reader.get_file_node("/Lotus/C.txt").is_none());
+
+    // Files are added to the first directory, which readers look up
+    let lotus = reader.get_directory_node("/Lotus").unwrap();
+    let names: Vec<_> = lotus.children().iter().map(|child| child.name()).collect();
+    assert_eq!(names, ["A.txt", "D.txt"]);
+}
+
+#[test]
+fn invalid_paths() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let mut writer = CachePairWriter::new(toc_path, cache_path, Some(true));
+    writer
+        .add_file("/Lotus/File.txt", b"data", Codec::Stored)
+        .unwrap();
+
+    let long_name = format!("/Lotus/{}", "a".repeat(65));
+    for path in [
+        "Lotus/Relative.txt",
+        "/Lotus/../Up.txt",
+        "/",
+        long_name.as_str(),
+    ] {
+        let result = writer.add_file(path, b"data", Codec::Stored);
+        assert!(matches!(result, Err(Error::InvalidPath(_))), "{}", path);
+    }
+
+    let result = writer.add_directory("/Lotus/File.txt");
+    assert!(matches!(result, Err(Error::InvalidPath(_))));
+
+    let result = writer.add_file("/Lotus", b"data", Codec::Stored);
+    assert!(matches!(result, Err(Error::InvalidPath(_))));
+}
+
+#[test]
+fn oodle_is_unsupported_pre_ensmallening() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(false));
+    let result = writer.add_file("/Lotus/File.txt", &pattern(100), Codec::Oodle);
+    assert!(matches!(result, Err(Error::UnsupportedCodec(Codec::Oodle))));
+    writer.write_toc().unwrap();
+
+    // The parent directory of the file is not added
+    let reader = read_back(&toc_path, &cache_path, Some(false));
+    assert!(reader.get_directory_node("/Lotus").is_none());
+}
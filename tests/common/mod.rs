@@ -0,0 +1,44 @@
+//! Fixtures shared by the integration tests.
+
+// Each test crate only uses some of the fixtures
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader};
+use tempfile::TempDir;
+
+/// Returns pseudo-random bytes that do not compress well.
+pub fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
+
+/// Returns bytes that compress well.
+pub fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+/// Returns the paths of the `Test` H cache pair in the directory.
+pub fn paths(dir: &TempDir) -> (PathBuf, PathBuf) {
+    (
+        dir.path().join("H.Test.toc"),
+        dir.path().join("H.Test.cache"),
+    )
+}
+
+/// Opens the cache pair and reads its TOC.
+pub fn read_back(
+    toc_path: &Path,
+    cache_path: &Path,
+    is_post_ensmallening: Option<bool>,
+) -> CachePairReader {
+    let mut reader = CachePairReader::new(toc_path.into(), cache_path.into(), is_post_ensmallening);
+    reader.read_toc().unwrap();
+    reader
+}
@@ -0,0 +1,146 @@
+//! Tests compacting cache pairs with [`CachePairReader::compact_to`] and
+//! [`CachePairReader::compact`].
+
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::FileNode;
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+use common::pattern;
+
+mod common;
+
+fn write(dir: &Path) -> (PathBuf, PathBuf) {
+    let toc_path = dir.join("H.Test.toc");
+    let cache_path = dir.join("H.Test.cache");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/A.txt", &pattern(5000), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Sub/B.bin", &pattern(0x40000 + 10), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/A.txt", b"replaced", Codec::Stored)
+        .unwrap();
+    writer.add_file("/Lotus/Empty", b"", Codec::Stored).unwrap();
+    writer.write_toc().unwrap();
+
+    (toc_path, cache_path)
+}
+
+fn assert_files(reader: &CachePairReader) {
+    assert_eq!(reader.files().len(), 3);
+    assert!(reader.replaced_versions("/Lotus/A.txt").is_empty());
+
+    let a = reader.get_file_node("/Lotus/A.txt").unwrap();
+    assert_eq!(reader.decompress_data(a).unwrap(), b"replaced");
+    let b = reader.get_file_node("/Lotus/Sub/B.bin").unwrap();
+    assert_eq!(reader.decompress_data(b).unwrap(), pattern(0x40000 + 10));
+    let empty = reader.get_file_node("/Lotus/Empty").unwrap();
+    assert!(reader.decompress_data(empty).unwrap().is_empty());
+}
+
+#[test]
+fn compact_to() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write(dir.path());
+    let toc = fs::read(&toc_path).unwrap();
+    let cache = fs::read(&cache_path).unwrap();
+
+    let reader = CachePairReader::new(toc_path.clone(), cache_path.clone(), None);
+    let new_toc_path = dir.path().join("H.New.toc");
+    let new_cache_path = dir.path().join("H.New.cache");
+    let report = reader.compact_to(&new_toc_path, &new_cache_path).unwrap();
+    assert_eq!(report.files, 3);
+    assert_eq!(report.directories, 2);
+    assert_eq!(report.dropped, 1);
+    assert_eq!(report.old_cache_len, cache.len() as u64);
+    assert_eq!(
+        report.new_cache_len,
+        fs::metadata(&new_cache_path).unwrap().len()
+    );
+    assert!(report.reclaimed_len() > 0);
+
+    // The original cache pair is untouched
+    assert_eq!(fs::read(&toc_path).unwrap(), toc);
+    assert_eq!(fs::read(&cache_path).unwrap(), cache);
+
+    let mut reader = CachePairReader::new(new_toc_path, new_cache_path, None);
+    reader.set_keep_history(true);
+    reader.read_toc().unwrap();
+    assert_files(&reader);
+    assert_eq!(reader.replaced_files().count(), 0);
+
+    let reader = CachePairReader::new(toc_path.clone(), cache_path.clone(), None);
+    let result = reader.compact_to(dir.path().join("H.Other.toc"), &cache_path);
+    assert!(matches!(result, Err(Error::InvalidPath(_))));
+    assert_eq!(fs::read(&cache_path).unwrap(), cache);
+}
+
+#[test]
+fn compact_in_place() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write(dir.path());
+
+    let mut reader = CachePairReader::new(toc_path.clone(), cache_path.clone(), None);
+    reader.read_toc().unwrap();
+    #[cfg(feature = "mmap")]
+    reader.map_cache().unwrap();
+
+    let report = reader.compact().unwrap();
+    assert_eq!(report.dropped, 1);
+    assert_eq!(
+        fs::metadata(&cache_path).unwrap().len(),
+        report.new_cache_len
+    );
+
+    // The TOC has been read again
+    assert!(reader.is_toc_loaded());
+    #[cfg(feature = "mmap")]
+    assert!(reader.is_cache_mapped());
+    assert_files(&reader);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn compact_unverified() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write(dir.path());
+
+    // Break the header of the second block of `B.bin`, which is copied as is
+    let mut reader = CachePairReader::new(toc_path.clone(), cache_path.clone(), None);
+    reader.read_toc().unwrap();
+    let b = reader.get_file_node("/Lotus/Sub/B.bin").unwrap();
+    let cache = fs::read(&cache_path).unwrap();
+    let first_header = b.cache_offset() as usize;
+    let first_comp_len =
+        u32::from_be_bytes(cache[first_header..first_header + 4].try_into().unwrap()) >> 2
+            & 0xFFFFFF;
+    let second_header = first_header as u64 + 8 + first_comp_len as u64;
+    let mut cache_file = OpenOptions::new().write(true).open(&cache_path).unwrap();
+    cache_file.seek(SeekFrom::Start(second_header + 7)).unwrap();
+    cache_file.write_all(&[0xF0]).unwrap();
+
+    let toc = fs::read(&toc_path).unwrap();
+    let cache = fs::read(&cache_path).unwrap();
+
+    match reader.compact() {
+        Err(Error::UnverifiedCompaction(report)) => {
+            assert_eq!(report.corrupt.len(), 1);
+            assert_eq!(report.corrupt[0].path, Path::new("/Lotus/Sub/B.bin"));
+        }
+        result => panic!("unexpected result: {result:?}"),
+    }
+
+    // Only the original cache pair remains, untouched
+    assert_eq!(fs::read(&toc_path).unwrap(), toc);
+    assert_eq!(fs::read(&cache_path).unwrap(), cache);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
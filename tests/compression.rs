@@ -0,0 +1,118 @@
+//! Round-trip property tests compressing data with the public encoders and decompressing it back.
+
+#![cfg(all(feature = "post_ensmallening", feature = "pre_ensmallening"))]
+
+use std::io::Cursor;
+
+use lotus_lib::compression::{
+    compress_post_ensmallening, compress_pre_ensmallening, decompress_post_ensmallening,
+    decompress_pre_ensmallening, Codec,
+};
+use proptest::prelude::*;
+
+const BLOCK_HEADER_LEN: usize = 8;
+const MAX_BLOCK_LEN: usize = 0x40000;
+
+/// Returns the compressed and decompressed lengths of every block, checking
+/// that the headers are well formed and that the blocks cover the data.
+fn block_lengths(compressed_data: &[u8]) -> Vec<(usize, usize)> {
+    let mut lengths = Vec::new();
+    let mut pos = 0;
+    while pos < compressed_data.len() {
+        let header = &compressed_data[pos..pos + BLOCK_HEADER_LEN];
+        assert_eq!(header[0], 0x80);
+        assert_eq!(header[7] & 0x0F, 0x1);
+
+        let num1 = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let num2 = u32::from_be_bytes(header[4..].try_into().unwrap());
+        let comp_len = (num1 >> 2 & 0xFFFFFF) as usize;
+        let decomp_len = (num2 >> 5 & 0xFFFFFF) as usize;
+        assert!(comp_len <= MAX_BLOCK_LEN);
+
+        lengths.push((comp_len, decomp_len));
+        pos += BLOCK_HEADER_LEN + comp_len;
+    }
+    assert_eq!(pos, compressed_data.len());
+    lengths
+}
+
+/// Returns data made of runs of a few bytes, which compresses well and spans
+/// several blocks.
+fn runs() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec((any::<u8>(), 1..0x8000usize), 0..64).prop_map(|runs| {
+        runs.into_iter()
+            .flat_map(|(byte, len)| std::iter::repeat_n(byte, len))
+            .collect()
+    })
+}
+
+fn round_trip_post_ensmallening(codec: Codec, data: &[u8]) -> Result<(), TestCaseError> {
+    let compressed_data = compress_post_ensmallening(codec, data).unwrap();
+
+    let decomp_len: usize = block_lengths(&compressed_data)
+        .iter()
+        .map(|(_, decomp_len)| decomp_len)
+        .sum();
+    prop_assert_eq!(decomp_len, data.len());
+
+    let decompressed_data = decompress_post_ensmallening(
+        compressed_data.len(),
+        data.len(),
+        &mut Cursor::new(&compressed_data),
+    )
+    .unwrap();
+    prop_assert_eq!(decompressed_data, data);
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn post_ensmallening_lz4(data in prop::collection::vec(any::<u8>(), 0..0x1000)) {
+        round_trip_post_ensmallening(Codec::Lz4, &data)?;
+    }
+
+    #[test]
+    fn post_ensmallening_lz4_blocks(data in runs()) {
+        round_trip_post_ensmallening(Codec::Lz4, &data)?;
+    }
+
+    #[test]
+    fn post_ensmallening_stored(data in prop::collection::vec(any::<u8>(), 0..0x1000)) {
+        round_trip_post_ensmallening(Codec::Stored, &data)?;
+    }
+
+    #[cfg(feature = "oodle")]
+    #[test]
+    #[ignore = "needs the real Oodle shared library at runtime"]
+    fn post_ensmallening_oodle(data in runs()) {
+        round_trip_post_ensmallening(Codec::Oodle, &data)?;
+    }
+
+    #[test]
+    fn pre_ensmallening(data in prop::collection::vec(any::<u8>(), 0..0x1000)) {
+        let compressed_data = compress_pre_ensmallening(&data);
+        let decompressed_data = decompress_pre_ensmallening(
+            compressed_data.len(),
+            data.len(),
+            &mut Cursor::new(&compressed_data),
+        )
+        .unwrap();
+        prop_assert_eq!(decompressed_data, data);
+    }
+}
+
+#[test]
+fn incompressible_blocks() {
+    // Noise does not fit in a block once compressed with LZ4, so the blocks
+    // are stored
+    let mut state = 1u32;
+    let data: Vec<u8> = (0..MAX_BLOCK_LEN * 2 + 1)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect();
+    round_trip_post_ensmallening(Codec::Lz4, &data).unwrap();
+}
@@ -0,0 +1,52 @@
+//! Tests decompressing byte ranges of cache entries with [`CachePairReader::decompress_range`].
+
+use lotus_lib::cache_pair::{CachePair, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+use common::{noise, paths, pattern, read_back};
+
+mod common;
+
+#[test]
+fn ranges_across_blocks() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let data = pattern(0x40000 * 2 + 5);
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/Blocks.bin", &data, Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Stored.bin", &noise(100, 1), Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, Some(true));
+    let node = reader.get_file_node("/Lotus/Blocks.bin").unwrap();
+    let len = data.len() as u64;
+    for range in [
+        0..len,
+        0x40000 - 3..0x40000 + 3,
+        0x40000..0x40000 * 2,
+        len - 5..len,
+        10..10,
+    ] {
+        let expected = &data[range.start as usize..range.end as usize];
+        assert_eq!(reader.decompress_range(&node, range).unwrap(), expected);
+    }
+
+    #[allow(clippy::reversed_empty_ranges)]
+    for range in [len - 1..len + 1, 5..4] {
+        let result = reader.decompress_range(&node, range);
+        assert!(matches!(result, Err(Error::InvalidRange { .. })));
+    }
+
+    let node = reader.get_file_node("/Lotus/Stored.bin").unwrap();
+    assert_eq!(
+        reader.decompress_range(&node, 90..100).unwrap(),
+        &noise(100, 1)[90..]
+    );
+}
@@ -0,0 +1,106 @@
+//! Tests decompressing blocks with backends registered at runtime.
+//!
+//! Backends are registered for the whole process, so every check lives in a single test.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::{
+    register_decompressor, unregister_decompressor, Codec, Decompressor, Lz4Decompressor,
+};
+use lotus_lib::toc::FileNode;
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+use common::pattern;
+
+mod common;
+
+const OODLE_MAGIC: u8 = 0x8C;
+const BLOCK_HEADER_LEN: u64 = 8;
+
+#[test]
+fn registered_decompressors() {
+    let dir = TempDir::new().unwrap();
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/Lz4.bin", &pattern(1000), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Oodle.bin", &pattern(2000), Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path.clone(), Some(true));
+    reader.read_toc().unwrap();
+    let lz4 = reader.get_file_node("/Lotus/Lz4.bin").unwrap();
+    let oodle = reader.get_file_node("/Lotus/Oodle.bin").unwrap();
+
+    // Make the single block of `Oodle.bin` look like an Oodle block
+    let mut cache_file = OpenOptions::new().write(true).open(&cache_path).unwrap();
+    cache_file
+        .seek(SeekFrom::Start(
+            oodle.cache_offset() as u64 + BLOCK_HEADER_LEN,
+        ))
+        .unwrap();
+    cache_file.write_all(&[OODLE_MAGIC]).unwrap();
+    drop(cache_file);
+
+    let result = reader.decompress_data(oodle.clone());
+    #[cfg(feature = "oodle")]
+    assert!(matches!(result, Err(Error::Oodle)), "{result:?}");
+    #[cfg(not(feature = "oodle"))]
+    assert!(
+        matches!(result, Err(Error::MissingDecompressor(Codec::Oodle))),
+        "{result:?}"
+    );
+
+    // A mock Oodle backend
+    let comp_len = oodle.comp_len() as usize - BLOCK_HEADER_LEN as usize;
+    let previous = register_decompressor(
+        Codec::Oodle,
+        Arc::new(
+            move |compressed_data: &[u8], decompressed_data: &mut [u8]| {
+                assert_eq!(compressed_data.len(), comp_len);
+                assert_eq!(compressed_data[0], OODLE_MAGIC);
+                decompressed_data.fill(0x42);
+                Ok(())
+            },
+        ),
+    );
+    assert!(previous.is_none());
+    assert_eq!(reader.decompress_data(oodle.clone()).unwrap(), [0x42; 2000]);
+
+    // A backend wrapping the built-in LZ4 one
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted_calls = Arc::clone(&calls);
+    register_decompressor(
+        Codec::Lz4,
+        Arc::new(
+            move |compressed_data: &[u8], decompressed_data: &mut [u8]| {
+                counted_calls.fetch_add(1, Ordering::Relaxed);
+                Lz4Decompressor.decompress(compressed_data, decompressed_data)
+            },
+        ),
+    );
+    assert_eq!(reader.decompress_data(lz4.clone()).unwrap(), pattern(1000));
+    let mut data = Vec::new();
+    let mut entry = reader.open_entry(&lz4).unwrap();
+    entry.read_to_end(&mut data).unwrap();
+    assert_eq!(data, pattern(1000));
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+    // The built-in backends are used again once unregistered
+    assert!(unregister_decompressor(Codec::Lz4).is_some());
+    assert!(unregister_decompressor(Codec::Oodle).is_some());
+    assert!(unregister_decompressor(Codec::Oodle).is_none());
+    assert_eq!(reader.decompress_data(lz4).unwrap(), pattern(1000));
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    assert!(reader.decompress_data(oodle).is_err());
+}
@@ -0,0 +1,109 @@
+//! Tests hashing the files of cache pairs and grouping the identical ones.
+
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter, HashAlgorithm};
+use lotus_lib::compression::Codec;
+use lotus_lib::package::{DedupOptions, PackageCollection, PackageType};
+use tempfile::TempDir;
+
+use common::pattern;
+
+mod common;
+
+fn write(dir: &Path, name: &str, files: &[(&str, &[u8], Codec)]) {
+    let mut writer = CachePairWriter::new(
+        dir.join(format!("H.{name}.toc")),
+        dir.join(format!("H.{name}.cache")),
+        Some(true),
+    );
+    for (path, data, codec) in files {
+        writer.add_file(*path, data, *codec).unwrap();
+    }
+    writer.write_toc().unwrap();
+}
+
+#[test]
+fn hash_entry() {
+    let dir = TempDir::new().unwrap();
+    let large = pattern(0x40000 * 2 + 3);
+    write(
+        dir.path(),
+        "Test",
+        &[
+            ("/Lotus/Lz4.bin", &large, Codec::Lz4),
+            ("/Lotus/Stored.bin", &large, Codec::Stored),
+            ("/Lotus/Other.bin", b"other", Codec::Lz4),
+        ],
+    );
+
+    let mut reader = CachePairReader::new(
+        dir.path().join("H.Test.toc"),
+        dir.path().join("H.Test.cache"),
+        None,
+    );
+    reader.read_toc().unwrap();
+
+    let hash = |path: &str| {
+        let node = reader.get_file_node(path).unwrap();
+        reader.hash_entry(&node, HashAlgorithm::Xxh3).unwrap()
+    };
+
+    // The hash only depends on the decompressed data
+    assert_eq!(hash("/Lotus/Lz4.bin"), hash("/Lotus/Stored.bin"));
+    assert_ne!(hash("/Lotus/Lz4.bin"), hash("/Lotus/Other.bin"));
+    assert_eq!(hash("/Lotus/Lz4.bin").algorithm(), HashAlgorithm::Xxh3);
+    assert_eq!(hash("/Lotus/Lz4.bin").to_string().len(), 32);
+
+    #[cfg(feature = "blake3")]
+    {
+        let node = reader.get_file_node("/Lotus/Lz4.bin").unwrap();
+        let hash = reader.hash_entry(&node, HashAlgorithm::Blake3).unwrap();
+        assert_eq!(hash.to_string(), blake3::hash(&large).to_hex().as_str());
+    }
+}
+
+#[test]
+fn dedup_collection() {
+    let dir = TempDir::new().unwrap();
+    let shared = pattern(1000);
+    write(
+        dir.path(),
+        "A",
+        &[
+            ("/Lotus/Shared.bin", &shared, Codec::Lz4),
+            ("/Lotus/Unique.bin", b"a", Codec::Stored),
+        ],
+    );
+    write(
+        dir.path(),
+        "B",
+        &[
+            ("/Lotus/Copy/Shared.bin", &shared, Codec::Stored),
+            ("/Lotus/Unique.bin", b"b", Codec::Stored),
+        ],
+    );
+
+    let mut collection = PackageCollection::<CachePairReader>::new(dir.path(), None).unwrap();
+    // Only the TOC of A is resident, B is read by the pass
+    collection.cache_pair("A", PackageType::H).unwrap().unwrap();
+
+    let options = DedupOptions {
+        threads: NonZeroUsize::new(2),
+        ..Default::default()
+    };
+    let report = collection.dedup(&options).unwrap();
+    assert_eq!(collection.residency().loads, 2);
+    assert!(report.is_success());
+    assert_eq!(report.blobs.len(), 3);
+    assert_eq!(report.saved_len(), shared.len() as u64);
+
+    let duplicates: Vec<_> = report.duplicates().collect();
+    assert_eq!(duplicates.len(), 1);
+    let entries = &duplicates[0].entries;
+    assert_eq!(entries[0].package_name, "A");
+    assert_eq!(entries[0].path, Path::new("/Lotus/Shared.bin"));
+    assert_eq!(entries[1].package_name, "B");
+    assert_eq!(entries[1].path, Path::new("/Lotus/Copy/Shared.bin"));
+}
@@ -0,0 +1,146 @@
+//! Tests comparing cache pairs written with [`CachePairWriter`] with [`diff`].
+
+use std::num::NonZeroI64;
+use std::path::Path;
+
+use lotus_lib::cache_pair::{
+    diff, diff_with, CachePair, CachePairReader, CachePairWriter, DiffOptions, FileChange,
+};
+use lotus_lib::compression::Codec;
+use tempfile::TempDir;
+
+fn write(dir: &Path, name: &str, files: &[(&str, &[u8])]) -> CachePairReader {
+    let toc_path = dir.join(format!("H.{name}.toc"));
+    let cache_path = dir.join(format!("H.{name}.cache"));
+
+    // Both versions share their timestamps, so that only the lengths and data differ
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer.set_timestamp(NonZeroI64::new(133_000_000_000_000_000).unwrap());
+    for (path, data) in files {
+        writer.add_file(*path, data, Codec::Lz4).unwrap();
+    }
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, None);
+    reader.read_toc().unwrap();
+    reader
+}
+
+#[test]
+fn diff_versions() {
+    let dir = TempDir::new().unwrap();
+    let old = write(
+        dir.path(),
+        "Old",
+        &[
+            ("/Lotus/Same.txt", b"same"),
+            ("/Lotus/Grown.txt", b"short"),
+            ("/Lotus/Removed/A.txt", b"a"),
+            ("/Lotus/Removed/B.txt", b"b"),
+            ("/Lotus/Moving/C.txt", b"moving"),
+        ],
+    );
+    let new = write(
+        dir.path(),
+        "New",
+        &[
+            ("/Lotus/Same.txt", b"same"),
+            ("/Lotus/Grown.txt", b"much longer"),
+            ("/Lotus/Added.txt", b"added"),
+            ("/Lotus/Moved/C.txt", b"moving"),
+        ],
+    );
+
+    let report = diff(&old, &new);
+    assert!(!report.is_empty());
+
+    let paths = |files: &[lotus_lib::cache_pair::DiffFile]| -> Vec<String> {
+        files
+            .iter()
+            .map(|file| file.path.to_string_lossy().into_owned())
+            .collect()
+    };
+    assert_eq!(paths(&report.added), ["/Lotus/Added.txt"]);
+    assert_eq!(
+        paths(&report.removed),
+        ["/Lotus/Removed/A.txt", "/Lotus/Removed/B.txt"]
+    );
+
+    assert_eq!(report.moved.len(), 1);
+    assert_eq!(report.moved[0].old.path, Path::new("/Lotus/Moving/C.txt"));
+    assert_eq!(report.moved[0].new.path, Path::new("/Lotus/Moved/C.txt"));
+
+    assert_eq!(report.modified.len(), 1);
+    let modified = &report.modified[0];
+    assert_eq!(modified.old.path, Path::new("/Lotus/Grown.txt"));
+    assert!(modified.changes.contains(&FileChange::Len));
+    assert!(!modified.changes.contains(&FileChange::Content));
+
+    assert_eq!(report.unchanged, 1);
+    assert!(diff(&old, &old).is_empty());
+}
+
+#[test]
+fn diff_content() {
+    let dir = TempDir::new().unwrap();
+    let old = write(dir.path(), "Old", &[("/Lotus/File.txt", b"abcd")]);
+    let new = write(dir.path(), "New", &[("/Lotus/File.txt", b"abce")]);
+
+    // The TOC entries are the same, only the data differs
+    assert!(diff(&old, &new).is_empty());
+
+    let options = DiffOptions {
+        compare_content: true,
+    };
+    let report = diff_with(&old, &new, &options).unwrap();
+    assert_eq!(report.modified.len(), 1);
+    assert!(report.modified[0].changes.contains(&FileChange::Content));
+}
+
+#[test]
+fn diff_content_across_chunks() {
+    let dir = TempDir::new().unwrap();
+    let data: Vec<u8> = (0..0x10000 * 3 + 7).map(|i| (i % 251) as u8).collect();
+    let mut changed = data.clone();
+    *changed.last_mut().unwrap() ^= 0xFF;
+
+    let old = write(
+        dir.path(),
+        "Old",
+        &[("/Lotus/Same.bin", &data), ("/Lotus/Changed.bin", &data)],
+    );
+    let new = write(
+        dir.path(),
+        "New",
+        &[("/Lotus/Same.bin", &data), ("/Lotus/Changed.bin", &changed)],
+    );
+
+    let options = DiffOptions {
+        compare_content: true,
+    };
+    let report = diff_with(&old, &new, &options).unwrap();
+    assert_eq!(report.unchanged, 1);
+    assert_eq!(report.modified.len(), 1);
+    let modified = &report.modified[0];
+    assert_eq!(modified.old.path, Path::new("/Lotus/Changed.bin"));
+    assert!(modified.changes.contains(&FileChange::Content));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn diff_to_json() {
+    let dir = TempDir::new().unwrap();
+    let old = write(dir.path(), "Old", &[("/Lotus/A.txt", b"a")]);
+    let new = write(dir.path(), "New", &[("/Lotus/A.txt", b"aa")]);
+
+    let report = diff(&old, &new);
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["modified"][0]["old"]["path"], "/Lotus/A.txt");
+    assert!(json["modified"][0]["changes"]
+        .as_array()
+        .unwrap()
+        .contains(&"len".into()));
+
+    let round_trip = serde_json::from_value(json).unwrap();
+    assert_eq!(report, round_trip);
+}
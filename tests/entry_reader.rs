@@ -0,0 +1,179 @@
+//! Tests streaming the data of cache entries with [`CachePairReader::open_entry`].
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter, EntryReader};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::FileNode;
+use tempfile::TempDir;
+
+use common::pattern;
+
+mod common;
+
+const BLOCK_LEN: usize = 0x40000;
+
+/// Writes the same data compressed and stored as is, and returns the reader
+/// of the cache pair along with the data.
+fn write_pair(dir: &TempDir) -> (CachePairReader, Vec<u8>) {
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    let data = pattern(BLOCK_LEN * 2 + 5);
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/Compressed.bin", &data, Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Stored.bin", &data, Codec::Stored)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Empty.bin", &[], Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, Some(true));
+    reader.read_toc().unwrap();
+    (reader, data)
+}
+
+fn read_len(entry: &mut EntryReader<'_>, len: usize) -> Vec<u8> {
+    let mut data = vec![0; len];
+    entry.read_exact(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn stored_entries_are_split_into_blocks() {
+    let dir = TempDir::new().unwrap();
+    let (reader, data) = write_pair(&dir);
+
+    let node = reader.get_file_node("/Lotus/Stored.bin").unwrap();
+    let blocks = reader.blocks(&node).unwrap();
+    let lens: Vec<_> = blocks.iter().map(|block| block.decomp_len).collect();
+    assert_eq!(lens, [BLOCK_LEN, BLOCK_LEN, 5]);
+
+    // The blocks are contiguous, without headers
+    let mut offset = node.cache_offset() as u64;
+    for block in &blocks {
+        assert_eq!(block.codec, Codec::Stored);
+        assert_eq!(block.comp_len, block.decomp_len);
+        assert_eq!(block.offset, offset);
+        offset += block.comp_len as u64;
+    }
+
+    let mut entry = reader.open_entry(&node).unwrap();
+    let mut read_data = Vec::new();
+    entry.read_to_end(&mut read_data).unwrap();
+    assert_eq!(read_data, data);
+
+    let node = reader.get_file_node("/Lotus/Empty.bin").unwrap();
+    assert!(reader.blocks(&node).unwrap().is_empty());
+    let mut entry = reader.open_entry(&node).unwrap();
+    assert!(entry.is_empty());
+    assert_eq!(entry.read(&mut [0; 16]).unwrap(), 0);
+}
+
+#[test]
+fn seek_across_blocks() {
+    let dir = TempDir::new().unwrap();
+    let (reader, data) = write_pair(&dir);
+
+    for path in ["/Lotus/Compressed.bin", "/Lotus/Stored.bin"] {
+        let node = reader.get_file_node(path).unwrap();
+        let mut entry = reader.open_entry(&node).unwrap();
+        assert_eq!(entry.len(), data.len() as u64);
+
+        // A read spanning a block boundary is served across both blocks
+        let start = BLOCK_LEN - 3;
+        assert_eq!(
+            entry.seek(SeekFrom::Start(start as u64)).unwrap(),
+            start as u64
+        );
+        assert_eq!(read_len(&mut entry, 6), data[start..start + 6], "{path}");
+
+        // Seeking backwards into a block that is not loaded anymore
+        assert_eq!(entry.seek(SeekFrom::Start(1)).unwrap(), 1);
+        assert_eq!(read_len(&mut entry, 4), data[1..5], "{path}");
+
+        // Seeking directly to the last block skips the ones in between
+        let start = BLOCK_LEN * 2 + 1;
+        entry.seek(SeekFrom::Start(start as u64)).unwrap();
+        assert_eq!(read_len(&mut entry, 4), data[start..], "{path}");
+    }
+}
+
+#[test]
+fn seek_from_end_and_current() {
+    let dir = TempDir::new().unwrap();
+    let (reader, data) = write_pair(&dir);
+    let len = data.len() as u64;
+
+    for path in ["/Lotus/Compressed.bin", "/Lotus/Stored.bin"] {
+        let node = reader.get_file_node(path).unwrap();
+        let mut entry = reader.open_entry(&node).unwrap();
+
+        assert_eq!(entry.seek(SeekFrom::End(-10)).unwrap(), len - 10);
+        assert_eq!(read_len(&mut entry, 10), data[data.len() - 10..], "{path}");
+        assert_eq!(entry.stream_position().unwrap(), len);
+
+        assert_eq!(
+            entry.seek(SeekFrom::Current(-(BLOCK_LEN as i64))).unwrap(),
+            len - BLOCK_LEN as u64
+        );
+        let start = data.len() - BLOCK_LEN;
+        assert_eq!(read_len(&mut entry, 8), data[start..start + 8], "{path}");
+
+        assert_eq!(entry.seek(SeekFrom::Current(8)).unwrap(), start as u64 + 16);
+        assert_eq!(read_len(&mut entry, 8), data[start + 16..start + 24]);
+    }
+}
+
+#[test]
+fn seek_before_start() {
+    let dir = TempDir::new().unwrap();
+    let (reader, data) = write_pair(&dir);
+
+    let node = reader.get_file_node("/Lotus/Stored.bin").unwrap();
+    let mut entry = reader.open_entry(&node).unwrap();
+    entry.seek(SeekFrom::Start(5)).unwrap();
+
+    for pos in [
+        SeekFrom::Current(-6),
+        SeekFrom::End(-(data.len() as i64) - 1),
+    ] {
+        let error = entry.seek(pos).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    // A failed seek leaves the position unchanged
+    assert_eq!(entry.stream_position().unwrap(), 5);
+    assert_eq!(read_len(&mut entry, 3), data[5..8]);
+}
+
+#[test]
+fn read_past_end() {
+    let dir = TempDir::new().unwrap();
+    let (reader, data) = write_pair(&dir);
+    let len = data.len() as u64;
+
+    for path in ["/Lotus/Compressed.bin", "/Lotus/Stored.bin"] {
+        let node = reader.get_file_node(path).unwrap();
+        let mut entry = reader.open_entry(&node).unwrap();
+
+        // Reads stop at the end of the entry
+        entry.seek(SeekFrom::End(-2)).unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(entry.read(&mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], data[data.len() - 2..]);
+        assert_eq!(entry.read(&mut buf).unwrap(), 0);
+
+        // Seeking past the end is allowed, but nothing can be read there
+        assert_eq!(entry.seek(SeekFrom::End(100)).unwrap(), len + 100);
+        assert_eq!(entry.read(&mut buf).unwrap(), 0);
+
+        entry.seek(SeekFrom::End(-4)).unwrap();
+        let error = entry.read_exact(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+}
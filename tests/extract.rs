@@ -0,0 +1,257 @@
+//! Tests extracting the files of cache pairs with [`CachePairReader::extract_to`].
+
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lotus_lib::cache_pair::{
+    CachePair, CachePairReader, CachePairWriter, ExtractOptions, ExtractProgress,
+};
+use lotus_lib::compression::Codec;
+use lotus_lib::package::{PackageCollection, PackageType};
+use lotus_lib::toc::FileNode;
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+const FILES: [(&str, &[u8], Codec); 4] = [
+    ("/Lotus/A.txt", b"a", Codec::Stored),
+    (
+        "/Lotus/Sub/B.txt",
+        b"bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+        Codec::Lz4,
+    ),
+    ("/Lotus/Sub/Deep/C.txt", b"c", Codec::Stored),
+    ("/Other/D.txt", b"d", Codec::Stored),
+];
+
+fn write_pair(dir: &Path, prefix: &str) -> (PathBuf, PathBuf) {
+    let toc_path = dir.join(format!("{prefix}.Test.toc"));
+    let cache_path = dir.join(format!("{prefix}.Test.cache"));
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    for (path, data, codec) in FILES {
+        writer.add_file(path, data, codec).unwrap();
+    }
+    writer.write_toc().unwrap();
+
+    (toc_path, cache_path)
+}
+
+fn read_pair(toc_path: PathBuf, cache_path: PathBuf) -> CachePairReader {
+    let mut reader = CachePairReader::new(toc_path, cache_path, None);
+    reader.read_toc().unwrap();
+    reader
+}
+
+/// Renames the entry with the given name in the TOC file.
+fn rename_entry(toc_path: &Path, name: &str, new_name: &str) {
+    let toc = fs::read(toc_path).unwrap();
+    let index = (8..toc.len())
+        .step_by(96)
+        .find(|offset| toc[offset + 32..].starts_with(name.as_bytes()))
+        .unwrap();
+
+    let mut name_bytes = [0; 64];
+    name_bytes[..new_name.len()].copy_from_slice(new_name.as_bytes());
+    let mut file = OpenOptions::new().write(true).open(toc_path).unwrap();
+    file.seek(SeekFrom::Start(index as u64 + 32)).unwrap();
+    file.write_all(&name_bytes).unwrap();
+}
+
+#[test]
+fn output_layout() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(dir.path(), "H");
+    let reader = read_pair(toc_path, cache_path);
+
+    let output = dir.path().join("out");
+    let report = reader
+        .extract_to(
+            &output,
+            |node| node.path().starts_with("/Lotus"),
+            &Default::default(),
+        )
+        .unwrap();
+    assert!(report.is_success());
+
+    let mut extracted = report.extracted;
+    extracted.sort();
+    assert_eq!(
+        extracted,
+        [
+            Path::new("/Lotus/A.txt"),
+            Path::new("/Lotus/Sub/B.txt"),
+            Path::new("/Lotus/Sub/Deep/C.txt"),
+        ]
+    );
+
+    for (path, data, _) in &FILES[..3] {
+        let output_path = output.join(path.trim_start_matches('/'));
+        assert_eq!(fs::read(output_path).unwrap(), *data);
+    }
+    assert!(!output.join("Other").exists());
+}
+
+#[test]
+fn package_layout() {
+    let dir = TempDir::new().unwrap();
+    write_pair(dir.path(), "H");
+    write_pair(dir.path(), "B");
+
+    let mut collection = PackageCollection::<CachePairReader>::new(dir.path(), None).unwrap();
+    collection.cache_pair("Test", PackageType::H).unwrap();
+    collection.cache_pair("Test", PackageType::B).unwrap();
+
+    let output = dir.path().join("out");
+    let package = collection.borrow("Test").unwrap();
+    let reports = package
+        .extract_all(&output, |_| true, &Default::default())
+        .unwrap();
+
+    let package_types: Vec<_> = reports
+        .iter()
+        .map(|(package_type, _)| *package_type)
+        .collect();
+    assert_eq!(package_types, [PackageType::H, PackageType::B]);
+    assert!(reports.iter().all(|(_, report)| report.is_success()));
+
+    // Each cache pair is extracted to the subdirectory of its type
+    assert_eq!(fs::read(output.join("H/Lotus/A.txt")).unwrap(), b"a");
+    assert_eq!(fs::read(output.join("B/Other/D.txt")).unwrap(), b"d");
+    assert!(!output.join("F").exists());
+}
+
+#[test]
+fn toc_not_read() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(dir.path(), "H");
+    let reader = CachePairReader::new(toc_path.clone(), cache_path, None);
+
+    let output = dir.path().join("out");
+    let result = reader.extract_to(&output, |_| true, &Default::default());
+    assert!(matches!(result, Err(Error::TocNotRead(path)) if path == toc_path));
+    assert!(!output.exists());
+}
+
+#[test]
+fn path_traversal() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(dir.path(), "H");
+    rename_entry(&toc_path, "Sub", "..");
+    let reader = read_pair(toc_path, cache_path);
+
+    let output = dir.path().join("out");
+    let report = reader
+        .extract_to(&output, |_| true, &Default::default())
+        .unwrap();
+
+    // The files under a `..` name are refused, the other ones are still extracted
+    let mut failed: Vec<_> = report.failed.iter().map(|(path, _)| path.clone()).collect();
+    failed.sort();
+    assert_eq!(
+        failed,
+        [
+            Path::new("/Lotus/../B.txt"),
+            Path::new("/Lotus/../Deep/C.txt"),
+        ]
+    );
+    assert!(report
+        .failed
+        .iter()
+        .all(|(_, error)| matches!(error, Error::InvalidPath(_))));
+    let mut extracted = report.extracted;
+    extracted.sort();
+    assert_eq!(
+        extracted,
+        [Path::new("/Lotus/A.txt"), Path::new("/Other/D.txt")]
+    );
+
+    assert!(!dir.path().join("B.txt").exists());
+    assert!(!dir.path().join("Deep").exists());
+    assert_eq!(fs::read(output.join("Other/D.txt")).unwrap(), b"d");
+}
+
+#[test]
+fn per_file_failures_and_progress() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(dir.path(), "H");
+    let reader = read_pair(toc_path, cache_path.clone());
+
+    // The last file lies past the end of the truncated cache file
+    let node = reader.get_file_node("/Other/D.txt").unwrap();
+    let cache_file = OpenOptions::new().write(true).open(&cache_path).unwrap();
+    cache_file.set_len(node.cache_offset() as u64).unwrap();
+
+    let progress = Mutex::new(Vec::new());
+    let on_progress = |event: ExtractProgress<'_>| {
+        progress.lock().unwrap().push((
+            event.node.path(),
+            event.error.is_some(),
+            event.completed,
+            event.total,
+        ));
+    };
+    let options = ExtractOptions {
+        threads: NonZeroUsize::new(3),
+        progress: Some(&on_progress),
+    };
+
+    let output = dir.path().join("out");
+    let report = reader.extract_to(&output, |_| true, &options).unwrap();
+    assert!(!report.is_success());
+    assert_eq!(report.extracted.len(), 3);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, Path::new("/Other/D.txt"));
+
+    // Every file is reported once, with a strictly increasing count
+    let mut progress = progress.into_inner().unwrap();
+    progress.sort_by_key(|(_, _, completed, _)| *completed);
+    let completed: Vec<_> = progress
+        .iter()
+        .map(|(_, _, completed, _)| *completed)
+        .collect();
+    assert_eq!(completed, [1, 2, 3, 4]);
+    assert!(progress.iter().all(|(.., total)| *total == 4));
+
+    let failed: Vec<_> = progress
+        .iter()
+        .filter(|(_, is_error, ..)| *is_error)
+        .map(|(path, ..)| path.clone())
+        .collect();
+    assert_eq!(failed, [Path::new("/Other/D.txt")]);
+}
+
+#[test]
+fn decompression_errors_keep_their_type() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(dir.path(), "H");
+    let reader = read_pair(toc_path, cache_path.clone());
+
+    // Replace the compressed data after the block header with LZ4 data decompressing to fewer
+    // bytes, made of a single run of literals
+    let node = reader.get_file_node("/Lotus/Sub/B.txt").unwrap();
+    let literal_len = node.comp_len() as usize - 8 - 5;
+    assert!(literal_len < 15);
+    let mut lz4_data = (literal_len as u32).to_le_bytes().to_vec();
+    lz4_data.push((literal_len as u8) << 4);
+    lz4_data.extend(vec![b'x'; literal_len]);
+
+    let mut cache_file = OpenOptions::new().write(true).open(&cache_path).unwrap();
+    cache_file
+        .seek(SeekFrom::Start(node.cache_offset() as u64 + 8))
+        .unwrap();
+    cache_file.write_all(&lz4_data).unwrap();
+
+    let output = dir.path().join("out");
+    let report = reader
+        .extract_to(&output, |node| node.name() == "B.txt", &Default::default())
+        .unwrap();
+    assert_eq!(report.failed.len(), 1);
+    assert!(
+        matches!(report.failed[0].1, Error::Lz4(_)),
+        "{:?}",
+        report.failed[0].1
+    );
+}
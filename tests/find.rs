@@ -0,0 +1,170 @@
+//! Tests searching the files of a cache pair with [`CachePairReader::find`].
+
+#[cfg(feature = "regex")]
+use lotus_lib::cache_pair::PatternSyntax;
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter, FindOptions};
+use lotus_lib::compression::Codec;
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+fn write_pair(dir: &TempDir) -> CachePairReader {
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    for path in [
+        "/Lotus/Sounds/Step.wav",
+        "/Lotus/Sounds/Footsteps/Grass.wav",
+        "/Lotus/Sounds/Footsteps/Metal.ogg",
+        "/Lotus/Textures/Step.png",
+        "/Lotus/Music.WAV",
+    ] {
+        writer
+            .add_file(path, path.as_bytes(), Codec::Stored)
+            .unwrap();
+    }
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, None);
+    reader.read_toc().unwrap();
+    reader
+}
+
+fn find(reader: &CachePairReader, pattern: &str, options: FindOptions) -> Vec<String> {
+    let mut paths: Vec<_> = reader
+        .find_with(pattern, &options)
+        .unwrap()
+        .map(|node| node.path().to_string_lossy().into_owned())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn glob() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+    let options = FindOptions::default();
+
+    // `*` does not match `/`, and paths are absolute
+    assert!(find(&reader, "*.wav", options).is_empty());
+    assert_eq!(
+        find(&reader, "/Lotus/Sounds/*.wav", options),
+        ["/Lotus/Sounds/Step.wav"]
+    );
+    assert_eq!(
+        find(&reader, "**/*.wav", options),
+        [
+            "/Lotus/Sounds/Footsteps/Grass.wav",
+            "/Lotus/Sounds/Step.wav"
+        ]
+    );
+    assert_eq!(
+        find(&reader, "/Lotus/Sounds/Footsteps/*.{wav,ogg}", options),
+        [
+            "/Lotus/Sounds/Footsteps/Grass.wav",
+            "/Lotus/Sounds/Footsteps/Metal.ogg"
+        ]
+    );
+    assert_eq!(
+        find(&reader, "/Lotus/*/Step.???", options),
+        ["/Lotus/Sounds/Step.wav", "/Lotus/Textures/Step.png"]
+    );
+
+    assert!(matches!(
+        reader.find("/Lotus/{Sounds"),
+        Err(Error::InvalidPattern(_))
+    ));
+}
+
+#[test]
+fn case_insensitive() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+
+    assert_eq!(
+        find(&reader, "**/*.wav", FindOptions::default()),
+        [
+            "/Lotus/Sounds/Footsteps/Grass.wav",
+            "/Lotus/Sounds/Step.wav"
+        ]
+    );
+
+    let options = FindOptions {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        find(&reader, "**/*.wav", options),
+        [
+            "/Lotus/Music.WAV",
+            "/Lotus/Sounds/Footsteps/Grass.wav",
+            "/Lotus/Sounds/Step.wav"
+        ]
+    );
+}
+
+#[test]
+fn base_directory() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+
+    // The search is narrowed to the literal leading directories
+    assert_eq!(
+        find(&reader, "/Lotus/Sounds/**/*.wav", FindOptions::default()),
+        [
+            "/Lotus/Sounds/Footsteps/Grass.wav",
+            "/Lotus/Sounds/Step.wav"
+        ]
+    );
+    assert!(find(&reader, "/Lotus/Missing/**", FindOptions::default()).is_empty());
+    assert!(find(&reader, "/lotus/sounds/**", FindOptions::default()).is_empty());
+
+    // Directories of another case are only found when the narrowing is disabled
+    let options = FindOptions {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        find(&reader, "/lotus/sounds/footsteps/*", options),
+        [
+            "/Lotus/Sounds/Footsteps/Grass.wav",
+            "/Lotus/Sounds/Footsteps/Metal.ogg"
+        ]
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+
+    // A regex matches any part of the path, including across directories
+    let options = FindOptions {
+        syntax: PatternSyntax::Regex,
+        ..Default::default()
+    };
+    assert_eq!(
+        find(&reader, r"Step\.", options),
+        ["/Lotus/Sounds/Step.wav", "/Lotus/Textures/Step.png"]
+    );
+    assert_eq!(
+        find(&reader, r"^/Lotus/Sounds/.*\.wav$", options),
+        [
+            "/Lotus/Sounds/Footsteps/Grass.wav",
+            "/Lotus/Sounds/Step.wav"
+        ]
+    );
+
+    let options = FindOptions {
+        syntax: PatternSyntax::Regex,
+        case_insensitive: true,
+    };
+    assert_eq!(find(&reader, r"music\.wav$", options), ["/Lotus/Music.WAV"]);
+
+    assert!(matches!(
+        reader.find_with("(", &options),
+        Err(Error::InvalidPattern(_))
+    ));
+}
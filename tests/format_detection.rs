@@ -0,0 +1,47 @@
+//! Tests detecting the ensmallening format of cache pairs from their entries.
+
+use lotus_lib::cache_pair::{CachePair, CachePairWriter};
+use lotus_lib::compression::Codec;
+use tempfile::TempDir;
+
+use common::{noise, paths, pattern, read_back};
+
+mod common;
+
+#[test]
+fn append_detects_format() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(false));
+    writer
+        .add_file("/Lotus/A.txt", &pattern(500), Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    // Appending keeps the pre-ensmallening format of the existing entries
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), None);
+    assert!(writer.is_post_ensmallening());
+    writer.read_toc().unwrap();
+    assert!(!writer.is_post_ensmallening());
+    writer
+        .add_file("/Lotus/B.txt", &pattern(600), Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, None);
+    assert!(!reader.is_post_ensmallening());
+    let node = reader.get_file_node("/Lotus/B.txt").unwrap();
+    assert_eq!(reader.decompress_data(node).unwrap(), pattern(600));
+
+    // Cache pairs without compressed entries default to post-ensmallening
+    let (toc_path, cache_path) = (dir.path().join("H.Raw.toc"), dir.path().join("H.Raw.cache"));
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(false));
+    writer
+        .add_file("/Lotus/Raw.bin", &noise(100, 4), Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, None);
+    assert!(reader.is_post_ensmallening());
+}
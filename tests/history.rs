@@ -0,0 +1,49 @@
+//! Tests reading the replaced versions of files with [`CachePairReader::replaced_versions`].
+
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::FileNode;
+use tempfile::TempDir;
+
+use common::{paths, pattern, read_back};
+
+mod common;
+
+#[test]
+fn replaced_versions() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/A.txt", b"first", Codec::Stored)
+        .unwrap();
+    writer
+        .add_file("/Lotus/A.txt", &pattern(800), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/A.txt", b"third", Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = read_back(&toc_path, &cache_path, Some(true));
+    assert!(reader.replaced_versions("/Lotus/A.txt").is_empty());
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, Some(true));
+    reader.set_keep_history(true);
+    reader.read_toc().unwrap();
+    assert_eq!(reader.files().len(), 1);
+
+    let versions = reader.replaced_versions("/Lotus/A.txt");
+    assert_eq!(versions.len(), 2);
+    assert_eq!(reader.replaced_files().count(), 2);
+    assert!(versions.iter().all(|version| version.timestamp() == 0));
+    assert_eq!(versions[0].path(), Path::new("/Lotus/A.txt"));
+    assert_eq!(reader.decompress_data(&versions[0]).unwrap(), b"first");
+    assert_eq!(reader.decompress_data(&versions[1]).unwrap(), pattern(800));
+
+    let current = reader.get_file_node("/Lotus/A.txt").unwrap();
+    assert_eq!(reader.decompress_data(current).unwrap(), b"third");
+}
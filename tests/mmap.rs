@@ -0,0 +1,97 @@
+//! Tests reading a cache pair whose cache file is memory-mapped with
+//! [`CachePairReader::map_cache`].
+
+#![cfg(feature = "mmap")]
+
+use std::borrow::Cow;
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::FileNode;
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+use common::{noise, paths, pattern, read_back};
+
+mod common;
+
+/// Writes a cache pair with a stored file followed by an LZ4 compressed file.
+fn write_pair(toc_path: &Path, cache_path: &Path) -> (Vec<u8>, Vec<u8>) {
+    let stored = noise(1000, 1);
+    let compressed = pattern(0x30000);
+
+    let mut writer = CachePairWriter::new(toc_path.into(), cache_path.into(), Some(true));
+    writer
+        .add_file("/Lotus/Stored.bin", &stored, Codec::Stored)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Compressed.bin", &compressed, Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    (stored, compressed)
+}
+
+#[test]
+fn mapped_reads() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+    let (stored, compressed) = write_pair(&toc_path, &cache_path);
+
+    let mut reader = read_back(&toc_path, &cache_path, None);
+    reader.map_cache().unwrap();
+    assert!(reader.is_cache_mapped());
+
+    // Stored files are borrowed from the mapping
+    let node = reader.get_file_node("/Lotus/Stored.bin").unwrap();
+    let data = reader.decompress_data_cow(&node).unwrap();
+    assert!(matches!(data, Cow::Borrowed(_)));
+    assert_eq!(data, stored);
+    assert_eq!(reader.get_data(node.clone()).unwrap(), stored);
+
+    let node = reader.get_file_node("/Lotus/Compressed.bin").unwrap();
+    assert!(node.comp_len() < node.len());
+    let data = reader.decompress_data_cow(&node).unwrap();
+    assert!(matches!(data, Cow::Owned(_)));
+    assert_eq!(data, compressed);
+    assert_eq!(reader.decompress_data(node.clone()).unwrap(), compressed);
+
+    // The raw data is the same as without the mapping
+    let mapped = reader.get_data(node.clone()).unwrap();
+    reader.unmap_cache();
+    assert!(!reader.is_cache_mapped());
+    assert_eq!(reader.get_data(node).unwrap(), mapped);
+}
+
+#[test]
+fn mapped_out_of_bounds() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+    write_pair(&toc_path, &cache_path);
+
+    // Cut the cache file in the middle of the stored file
+    let cache_file = OpenOptions::new().write(true).open(&cache_path).unwrap();
+    cache_file.set_len(500).unwrap();
+
+    let mut reader = read_back(&toc_path, &cache_path, Some(true));
+    reader.map_cache().unwrap();
+
+    let node = reader.get_file_node("/Lotus/Stored.bin").unwrap();
+    for result in [
+        reader.get_data(node.clone()).map(|_| ()),
+        reader.get_data_cow(&node).map(|_| ()),
+        reader.decompress_data_cow(&node).map(|_| ()),
+    ] {
+        assert!(matches!(
+            result,
+            Err(Error::EntryOutOfBounds {
+                index: None,
+                cache_offset: 0,
+                comp_len: 1000,
+                cache_len: 500,
+            })
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+//! Tests listing the packages of a directory with [`PackageCollection::new`].
+
+use std::fs::File;
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader};
+use lotus_lib::package::{Locale, PackageCollection, PackageType};
+use tempfile::TempDir;
+
+/// Creates empty files with the given names, which is enough to list packages.
+fn touch(dir: &Path, file_names: &[&str]) {
+    for file_name in file_names {
+        File::create(dir.join(file_name)).unwrap();
+    }
+}
+
+fn collection(file_names: &[&str]) -> (TempDir, PackageCollection<CachePairReader>) {
+    let dir = TempDir::new().unwrap();
+    touch(dir.path(), file_names);
+    let collection = PackageCollection::new(dir.path(), None).unwrap();
+    (dir, collection)
+}
+
+fn package_names(collection: &PackageCollection<CachePairReader>) -> Vec<&str> {
+    collection
+        .packages()
+        .iter()
+        .map(|package| package.name().as_str())
+        .collect()
+}
+
+#[test]
+fn discovery() {
+    let (_dir, collection) = collection(&[
+        "H.Misc.toc",
+        "H.Misc.cache",
+        "F.Misc.toc",
+        "F.Misc.cache",
+        "B.Texture.toc",
+        "B.Texture.cache",
+        "H.Font.Extra.toc",
+        "H.Font.Extra.cache",
+        // Not cache pair files
+        "X.Misc.toc",
+        "H..toc",
+        "H.Misc.txt",
+        "Misc.toc",
+        "H.Misc",
+    ]);
+
+    assert_eq!(
+        package_names(&collection),
+        ["Font.Extra", "Misc", "Texture"]
+    );
+
+    let misc = collection.borrow("Misc").unwrap();
+    assert!(misc.borrow(PackageType::H).is_some());
+    assert!(misc.borrow(PackageType::F).is_some());
+    assert!(misc.borrow(PackageType::B).is_none());
+
+    let texture = collection.borrow("Texture").unwrap();
+    let cache_pair = texture.borrow(PackageType::B).unwrap();
+    assert!(cache_pair.toc_path().ends_with("B.Texture.toc"));
+    assert!(cache_pair.cache_path().ends_with("B.Texture.cache"));
+}
+
+#[test]
+fn incomplete_pairs() {
+    let (_dir, collection) =
+        collection(&["H.Misc.toc", "H.Misc.cache", "F.Misc.toc", "B.Misc.cache"]);
+
+    // A cache pair missing one of its files is left out, since its TOC cannot be read
+    let misc = collection.borrow("Misc").unwrap();
+    assert!(misc.borrow(PackageType::H).is_some());
+    assert!(misc.borrow(PackageType::F).is_none());
+    assert!(misc.borrow(PackageType::B).is_none());
+}
+
+#[test]
+fn orphans() {
+    let (_dir, collection) = collection(&[
+        "H.Misc.toc",
+        "H.Misc.cache",
+        "F.Misc.toc",
+        "F.Misc.cache",
+        "F.OnlyF.toc",
+        "F.OnlyF.cache",
+        "B.OnlyB.toc",
+        "B.OnlyB.cache",
+        "H.HalfPair.cache",
+    ]);
+
+    assert_eq!(
+        package_names(&collection),
+        ["HalfPair", "Misc", "OnlyB", "OnlyF"]
+    );
+
+    // A package is orphan when its H cache pair is missing or incomplete
+    let orphans: Vec<_> = collection
+        .orphans()
+        .map(|package| package.name().as_str())
+        .collect();
+    assert_eq!(orphans, ["HalfPair", "OnlyB", "OnlyF"]);
+
+    let only_f = collection.borrow("OnlyF").unwrap();
+    assert!(only_f.is_orphan());
+    assert!(only_f.borrow(PackageType::H).is_none());
+    assert!(only_f.borrow(PackageType::F).is_some());
+    assert!(!collection.borrow("Misc").unwrap().is_orphan());
+}
+
+#[test]
+fn locale_suffixes() {
+    let (_dir, collection) = collection(&[
+        "H.Misc.toc",
+        "H.Misc_fr.toc",
+        "H.Misc_DE.toc",
+        "H.Misc_jp.toc",
+        "H.Misc_tc.toc",
+        "H.Font_en.toc",
+    ]);
+
+    let locales: Vec<_> = collection
+        .packages()
+        .iter()
+        .map(|package| (package.base_name(), package.locale()))
+        .collect();
+    assert_eq!(
+        locales,
+        [
+            ("Font", Some(Locale::English)),
+            ("Misc", None),
+            ("Misc", Some(Locale::German)),
+            ("Misc", Some(Locale::French)),
+            ("Misc", Some(Locale::Japanese)),
+            ("Misc", Some(Locale::TraditionalChinese)),
+        ]
+    );
+
+    let french = collection.borrow_localized("Misc", Locale::French).unwrap();
+    assert_eq!(french.name(), "Misc_fr");
+    assert!(collection
+        .borrow_localized("Misc", Locale::Korean)
+        .is_none());
+    assert!(collection
+        .borrow_localized("Font", Locale::French)
+        .is_none());
+
+    let variants: Vec<_> = collection
+        .variants("Misc")
+        .map(|package| package.name().as_str())
+        .collect();
+    assert_eq!(
+        variants,
+        ["Misc", "Misc_DE", "Misc_fr", "Misc_jp", "Misc_tc"]
+    );
+}
+
+#[test]
+fn language_prefixes() {
+    let (_dir, collection) = collection(&[
+        "H.LanguageFR.toc",
+        "H.Languageko.toc",
+        "H.LanguageJP.toc",
+        "H.Language.toc",
+    ]);
+
+    let french = collection
+        .borrow_localized("Language", Locale::French)
+        .unwrap();
+    assert_eq!(french.name(), "LanguageFR");
+    assert_eq!(
+        collection
+            .borrow_localized("Language", Locale::Korean)
+            .unwrap()
+            .name(),
+        "Languageko"
+    );
+    assert_eq!(
+        collection
+            .borrow_localized("Language", Locale::Japanese)
+            .unwrap()
+            .name(),
+        "LanguageJP"
+    );
+
+    let language = collection.borrow("Language").unwrap();
+    assert_eq!(language.base_name(), "Language");
+    assert_eq!(language.locale(), None);
+    assert_eq!(collection.variants("Language").count(), 4);
+}
+
+#[test]
+fn not_locales() {
+    let names = [
+        "Misc_fra",
+        "Misc_fr_Old",
+        "Misc_",
+        "_fr",
+        "LanguageSettings",
+        "Languages",
+        "MiscFR",
+        "Misc-fr",
+    ];
+    let file_names: Vec<_> = names.iter().map(|name| format!("H.{name}.toc")).collect();
+    let file_names: Vec<_> = file_names.iter().map(String::as_str).collect();
+    let (_dir, collection) = collection(&file_names);
+
+    for name in names {
+        let package = collection.borrow(name).unwrap();
+        assert_eq!(package.locale(), None, "{name}");
+        assert_eq!(package.base_name(), name, "{name}");
+    }
+}
@@ -0,0 +1,187 @@
+//! Tests the resident TOCs of a [`PackageCollection`] accessed with
+//! [`PackageCollection::cache_pair`].
+
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::package::{PackageCollection, PackageType, ResidencyStats};
+use tempfile::TempDir;
+
+/// Writes the `A`, `B` and `C` packages, each with an H cache pair of the same size.
+fn write_collection(dir: &Path) -> PackageCollection<CachePairReader> {
+    for name in ["A", "B", "C"] {
+        let mut writer = CachePairWriter::new(
+            dir.join(format!("H.{name}.toc")),
+            dir.join(format!("H.{name}.cache")),
+            Some(true),
+        );
+        for file in ["One", "Two", "Three"] {
+            writer
+                .add_file(format!("/Lotus/{file}.txt"), b"data", Codec::Stored)
+                .unwrap();
+        }
+        writer.write_toc().unwrap();
+    }
+    PackageCollection::new(dir, None).unwrap()
+}
+
+fn access(collection: &mut PackageCollection<CachePairReader>, package_name: &str) {
+    let cache_pair = collection.cache_pair(package_name, PackageType::H).unwrap();
+    assert!(cache_pair.unwrap().is_toc_loaded());
+}
+
+/// Returns the names of the resident packages, from the least to the most recently accessed.
+fn resident(stats: &ResidencyStats) -> Vec<&str> {
+    stats
+        .resident
+        .iter()
+        .map(|resident_toc| resident_toc.package_name.as_str())
+        .collect()
+}
+
+fn toc_memory_usage(collection: &mut PackageCollection<CachePairReader>) -> usize {
+    access(collection, "A");
+    let memory_usage = collection.residency().memory_usage;
+    collection.unload_all();
+    memory_usage
+}
+
+#[test]
+fn stats() {
+    let dir = TempDir::new().unwrap();
+    let mut collection = write_collection(dir.path());
+
+    assert!(collection
+        .cache_pair("Missing", PackageType::H)
+        .unwrap()
+        .is_none());
+    assert!(collection
+        .cache_pair("A", PackageType::B)
+        .unwrap()
+        .is_none());
+
+    access(&mut collection, "A");
+    access(&mut collection, "B");
+    access(&mut collection, "A");
+
+    let stats = collection.residency();
+    assert_eq!(resident(&stats), ["B", "A"]);
+    assert_eq!((stats.loads, stats.hits, stats.evictions), (2, 1, 0));
+    assert_eq!(stats.memory_budget, None);
+    assert!(stats
+        .resident
+        .iter()
+        .all(|resident_toc| resident_toc.is_tracked));
+    assert!(stats
+        .resident
+        .iter()
+        .all(|resident_toc| resident_toc.memory_usage > 0));
+    assert_eq!(
+        stats.memory_usage,
+        stats
+            .resident
+            .iter()
+            .map(|resident_toc| resident_toc.memory_usage)
+            .sum::<usize>()
+    );
+
+    // TOCs read directly count as the least recently accessed
+    let package = collection.borrow_mut("C").unwrap();
+    package
+        .borrow_mut(PackageType::H)
+        .unwrap()
+        .read_toc()
+        .unwrap();
+    let stats = collection.residency();
+    assert_eq!(resident(&stats), ["C", "B", "A"]);
+    assert!(!stats.resident[0].is_tracked);
+    assert_eq!(stats.loads, 2);
+
+    collection.unload_all();
+    let stats = collection.residency();
+    assert!(stats.resident.is_empty());
+    assert_eq!(stats.memory_usage, 0);
+}
+
+#[test]
+fn lru_eviction() {
+    let dir = TempDir::new().unwrap();
+    let mut collection = write_collection(dir.path());
+    let toc_memory_usage = toc_memory_usage(&mut collection);
+
+    // Room for two TOCs
+    collection.set_memory_budget(Some(toc_memory_usage * 2));
+    assert_eq!(collection.memory_budget(), Some(toc_memory_usage * 2));
+
+    access(&mut collection, "A");
+    access(&mut collection, "B");
+    access(&mut collection, "A");
+    access(&mut collection, "C");
+
+    // B is the least recently accessed
+    let stats = collection.residency();
+    assert_eq!(resident(&stats), ["A", "C"]);
+    assert_eq!(stats.evictions, 1);
+    assert!(stats.memory_usage <= toc_memory_usage * 2);
+
+    access(&mut collection, "B");
+    let stats = collection.residency();
+    assert_eq!(resident(&stats), ["C", "B"]);
+    assert_eq!(stats.evictions, 2);
+    assert_eq!(stats.loads, 5);
+}
+
+#[test]
+fn budget_enforcement() {
+    let dir = TempDir::new().unwrap();
+    let mut collection = write_collection(dir.path());
+    let toc_memory_usage = toc_memory_usage(&mut collection);
+
+    for name in ["A", "B", "C"] {
+        access(&mut collection, name);
+    }
+    assert_eq!(collection.residency().resident.len(), 3);
+
+    // Lowering the budget evicts right away
+    collection.set_memory_budget(Some(toc_memory_usage));
+    let stats = collection.residency();
+    assert_eq!(resident(&stats), ["C"]);
+    assert_eq!(stats.evictions, 2);
+
+    // The TOC being accessed is kept even if it does not fit on its own
+    collection.set_memory_budget(Some(0));
+    assert!(collection.residency().resident.is_empty());
+    access(&mut collection, "A");
+    assert_eq!(resident(&collection.residency()), ["A"]);
+
+    collection.set_memory_budget(None);
+    access(&mut collection, "B");
+    access(&mut collection, "C");
+    let stats = collection.residency();
+    assert_eq!(resident(&stats), ["A", "B", "C"]);
+    assert_eq!(stats.evictions, 3);
+}
+
+#[test]
+fn take_package() {
+    let dir = TempDir::new().unwrap();
+    let mut collection = write_collection(dir.path());
+
+    access(&mut collection, "C");
+    access(&mut collection, "A");
+    access(&mut collection, "B");
+    let package = collection.take("A").unwrap();
+    assert!(package.borrow(PackageType::H).unwrap().is_toc_loaded());
+
+    // The packages after the removed one keep their last access
+    let stats = collection.residency();
+    assert_eq!(resident(&stats), ["C", "B"]);
+    assert!(stats
+        .resident
+        .iter()
+        .all(|resident_toc| resident_toc.is_tracked));
+
+    access(&mut collection, "C");
+    assert_eq!(resident(&collection.residency()), ["B", "C"]);
+}
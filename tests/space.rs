@@ -0,0 +1,94 @@
+//! Tests reporting the space usage of cache pairs with [`CachePairReader::space_report`].
+
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter, Extent, SpaceUsage};
+use lotus_lib::compression::Codec;
+use tempfile::TempDir;
+
+const TOC_HEADER_LEN: u64 = 8;
+const TOC_ENTRY_LEN: u64 = 96;
+
+fn overwrite(path: &Path, offset: u64, data: &[u8]) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(data).unwrap();
+}
+
+#[test]
+fn space_report() {
+    let dir = TempDir::new().unwrap();
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    // Stored files keep the same compressed and decompressed lengths
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/A.txt", b"aaaa", Codec::Stored)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Sub/B.TXT", b"bbbbbb", Codec::Stored)
+        .unwrap();
+    writer.add_file("/Lotus/C", b"cc", Codec::Stored).unwrap();
+    writer
+        .add_file("/Lotus/A.txt", b"AAAAAAAA", Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let reader = CachePairReader::new(toc_path.clone(), cache_path.clone(), None);
+    let report = reader.space_report().unwrap();
+    assert_eq!(report.cache_len, 20);
+    let usage = |files, len| SpaceUsage {
+        files,
+        comp_len: len,
+        len,
+    };
+    assert_eq!(report.live, usage(3, 16));
+    assert_eq!(report.replaced, usage(1, 4));
+    assert_eq!(report.live.compression_ratio(), 1.0);
+    assert_eq!(report.reclaimable_len, 4);
+    assert!(report.gaps.is_empty());
+    assert!(report.overlaps.is_empty());
+
+    let directories: Vec<_> = report.directories.into_iter().collect();
+    assert_eq!(
+        directories,
+        [
+            (PathBuf::from("/Lotus"), usage(2, 10)),
+            (PathBuf::from("/Lotus/Sub"), usage(1, 6)),
+        ]
+    );
+    let extensions: Vec<_> = report.extensions.into_iter().collect();
+    assert_eq!(
+        extensions,
+        [(String::new(), usage(1, 2)), ("txt".into(), usage(2, 14))]
+    );
+
+    // Entries are `/Lotus`, `A.txt`, `/Lotus/Sub`, `B.TXT`, `C` and the new
+    // `A.txt`, move `C` into the replaced `A.txt` and leave unused bytes
+    overwrite(
+        &toc_path,
+        TOC_HEADER_LEN + 4 * TOC_ENTRY_LEN,
+        &2i64.to_le_bytes(),
+    );
+    overwrite(&cache_path, 20, b"xyz");
+
+    let report = reader.space_report().unwrap();
+    assert_eq!(report.cache_len, 23);
+    assert_eq!(report.reclaimable_len, 7);
+    assert_eq!(
+        report.gaps,
+        [Extent { offset: 10, len: 2 }, Extent { offset: 20, len: 3 },]
+    );
+    assert_eq!(report.gaps_len(), 5);
+
+    assert_eq!(report.overlaps.len(), 1);
+    let overlap = &report.overlaps[0];
+    assert_eq!(overlap.first, Path::new("/Lotus/A.txt"));
+    assert!(overlap.first_is_replaced);
+    assert_eq!(overlap.second, Path::new("/Lotus/C"));
+    assert!(!overlap.second_is_replaced);
+    assert_eq!(overlap.extent, Extent { offset: 2, len: 2 });
+}
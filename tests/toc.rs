@@ -0,0 +1,219 @@
+//! Tests reading malformed TOC files with [`CachePairReader::read_toc`].
+
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::FileNode;
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+const TOC_HEADER_LEN: u64 = 8;
+const TOC_ENTRY_LEN: u64 = 96;
+
+/// Returns the offset of the TOC entry at the given index.
+fn entry_offset(index: u64) -> u64 {
+    TOC_HEADER_LEN + index * TOC_ENTRY_LEN
+}
+
+fn overwrite(path: &Path, offset: u64, data: &[u8]) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(data).unwrap();
+}
+
+/// Writes a cache pair with the `/Lotus` directory followed by a single file,
+/// replaced once if `replace` is set.
+fn write_pair(dir: &TempDir, replace: bool) -> (PathBuf, PathBuf) {
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/File.bin", &[1; 100], Codec::Lz4)
+        .unwrap();
+    if replace {
+        writer
+            .add_file("/Lotus/File.bin", &[2; 200], Codec::Lz4)
+            .unwrap();
+    }
+    writer.write_toc().unwrap();
+
+    (toc_path, cache_path)
+}
+
+fn read_toc(toc_path: &Path, cache_path: &Path) -> (CachePairReader, lotus_lib::Result<()>) {
+    let mut reader = CachePairReader::new(toc_path.to_path_buf(), cache_path.to_path_buf(), None);
+    let result = reader.read_toc();
+    (reader, result)
+}
+
+#[test]
+fn invalid_header() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(&dir, false);
+
+    overwrite(&toc_path, 0, &0xDEADBEEFu32.to_le_bytes());
+    let (reader, result) = read_toc(&toc_path, &cache_path);
+    assert!(matches!(result, Err(Error::InvalidMagic(0xDEADBEEF))));
+    assert!(!reader.is_toc_loaded());
+
+    let (toc_path, cache_path) = write_pair(&dir, false);
+    overwrite(&toc_path, 4, &19u32.to_le_bytes());
+    let (reader, result) = read_toc(&toc_path, &cache_path);
+    assert!(matches!(result, Err(Error::UnsupportedVersion(19))));
+    assert!(!reader.is_toc_loaded());
+}
+
+#[test]
+fn parent_index_out_of_range() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(&dir, false);
+
+    overwrite(&toc_path, entry_offset(1) + 28, &42i32.to_le_bytes());
+    let (reader, result) = read_toc(&toc_path, &cache_path);
+    assert!(matches!(
+        result,
+        Err(Error::ParentIndexOutOfRange {
+            index: 1,
+            parent_index: 42
+        })
+    ));
+    assert!(!reader.is_toc_loaded());
+
+    overwrite(&toc_path, entry_offset(1) + 28, &(-1i32).to_le_bytes());
+    let (_, result) = read_toc(&toc_path, &cache_path);
+    assert!(matches!(
+        result,
+        Err(Error::ParentIndexOutOfRange {
+            index: 1,
+            parent_index: -1
+        })
+    ));
+}
+
+#[test]
+fn negative_lengths() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(&dir, false);
+
+    // Both lengths are negative, which used to overflow when allocating the data
+    overwrite(&toc_path, entry_offset(1) + 16, &(-1i32).to_le_bytes());
+    overwrite(&toc_path, entry_offset(1) + 20, &(-1i32).to_le_bytes());
+    let (reader, result) = read_toc(&toc_path, &cache_path);
+    assert!(matches!(
+        result,
+        Err(Error::InvalidEntryLength {
+            index: Some(1),
+            len: -1
+        })
+    ));
+    assert!(!reader.is_toc_loaded());
+
+    // Only the decompressed length is negative
+    overwrite(&toc_path, entry_offset(1) + 16, &100i32.to_le_bytes());
+    overwrite(&toc_path, entry_offset(1) + 20, &(-2i32).to_le_bytes());
+    let (_, result) = read_toc(&toc_path, &cache_path);
+    assert!(matches!(
+        result,
+        Err(Error::InvalidEntryLength {
+            index: Some(1),
+            len: -2
+        })
+    ));
+}
+
+#[test]
+fn negative_lengths_in_history() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(&dir, true);
+
+    // The replaced file is only read with its history
+    overwrite(&toc_path, entry_offset(1) + 16, &(-3i32).to_le_bytes());
+    let (_, result) = read_toc(&toc_path, &cache_path);
+    result.unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, None);
+    reader.set_keep_history(true);
+    assert!(matches!(
+        reader.read_toc(),
+        Err(Error::InvalidEntryLength {
+            index: Some(1),
+            len: -3
+        })
+    ));
+}
+
+/// A file node with arbitrary lengths, which is not validated by a TOC.
+struct RawFileNode {
+    comp_len: i32,
+    len: i32,
+}
+
+impl FileNode for RawFileNode {
+    fn cache_offset(&self) -> i64 {
+        0
+    }
+
+    fn timestamp(&self) -> i64 {
+        1
+    }
+
+    fn comp_len(&self) -> i32 {
+        self.comp_len
+    }
+
+    fn len(&self) -> i32 {
+        self.len
+    }
+}
+
+/// Returns whether the result is an invalid length error for a file node.
+fn is_invalid_length<T>(result: lotus_lib::Result<T>, expected: i32) -> bool {
+    matches!(result, Err(Error::InvalidEntryLength { index: None, len }) if len == expected)
+}
+
+fn check_node_lengths(reader: &CachePairReader) {
+    let node = RawFileNode {
+        comp_len: -1,
+        len: -1,
+    };
+    assert!(is_invalid_length(reader.get_data(&node), -1));
+    assert!(is_invalid_length(reader.get_data_cow(&node), -1));
+    assert!(is_invalid_length(reader.decompress_data(&node), -1));
+    assert!(is_invalid_length(reader.open_entry(&node), -1));
+
+    let node = RawFileNode {
+        comp_len: 10,
+        len: -5,
+    };
+    assert!(is_invalid_length(reader.decompress_data(&node), -5));
+    assert!(is_invalid_length(reader.decompress_data_cow(&node), -5));
+    assert!(is_invalid_length(reader.open_entry(&node), -5));
+    assert!(is_invalid_length(
+        reader.hash_entry(&node, Default::default()),
+        -5
+    ));
+}
+
+#[test]
+fn negative_node_lengths() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(&dir, false);
+    let (reader, result) = read_toc(&toc_path, &cache_path);
+    result.unwrap();
+    check_node_lengths(&reader);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn negative_node_lengths_mapped() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = write_pair(&dir, false);
+    let (mut reader, result) = read_toc(&toc_path, &cache_path);
+    result.unwrap();
+    reader.map_cache().unwrap();
+    check_node_lengths(&reader);
+}
@@ -0,0 +1,68 @@
+//! Tests caching parsed TOCs in an index file with [`CachePairReader::read_toc_with_index`].
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use tempfile::TempDir;
+
+use common::{paths, pattern};
+
+mod common;
+
+#[test]
+fn index_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let (toc_path, cache_path) = paths(&dir);
+    let index_path = dir.path().join("H.Test.toc.idx");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/A.txt", &pattern(500), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/A.txt", b"second", Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let open = || CachePairReader::new(toc_path.clone(), cache_path.clone(), None);
+
+    // The index is written on the first read and loaded on the next ones
+    let mut reader = open();
+    assert!(!reader.read_toc_with_index(&index_path).unwrap());
+    assert!(index_path.exists());
+    let mut reader = open();
+    assert!(reader.read_toc_with_index(&index_path).unwrap());
+    assert!(reader.is_post_ensmallening());
+    assert_eq!(reader.version(), Some(20));
+    let node = reader.get_file_node("/Lotus/A.txt").unwrap();
+    assert_eq!(reader.decompress_data(node).unwrap(), b"second");
+
+    // An index without the replaced versions is stale when the history is kept
+    let mut reader = open();
+    reader.set_keep_history(true);
+    assert!(!reader.read_toc_with_index(&index_path).unwrap());
+    assert_eq!(reader.replaced_versions("/Lotus/A.txt").len(), 1);
+    let mut reader = open();
+    reader.set_keep_history(true);
+    assert!(reader.read_toc_with_index(&index_path).unwrap());
+    assert_eq!(reader.replaced_versions("/Lotus/A.txt").len(), 1);
+
+    // The index is stale once the TOC changes
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer.read_toc().unwrap();
+    writer
+        .add_file("/Lotus/B.txt", b"new", Codec::Stored)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let mut reader = open();
+    assert!(!reader.read_toc_with_index(&index_path).unwrap());
+    assert!(reader.get_file_node("/Lotus/B.txt").is_some());
+
+    // A corrupt index is rewritten
+    std::fs::write(&index_path, b"corrupt").unwrap();
+    let mut reader = open();
+    assert!(!reader.read_toc_with_index(&index_path).unwrap());
+    let mut reader = open();
+    assert!(reader.read_toc_with_index(&index_path).unwrap());
+    assert_eq!(reader.files().len(), 2);
+}
@@ -0,0 +1,112 @@
+//! Tests checking the integrity of corrupted cache pairs with [`CachePairReader::verify`].
+
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::{FileNode, NodeKind};
+use lotus_lib::Error;
+use tempfile::TempDir;
+
+use common::pattern;
+
+mod common;
+
+const TOC_HEADER_LEN: u64 = 8;
+const TOC_ENTRY_LEN: u64 = 96;
+
+fn overwrite(path: &Path, offset: u64, data: &[u8]) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(data).unwrap();
+}
+
+#[test]
+fn verify_corruption() {
+    let dir = TempDir::new().unwrap();
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    writer
+        .add_file("/Lotus/Blocks.bin", &pattern(0x40000 * 2 + 7), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Named.bin", &pattern(100), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Orphan.bin", &pattern(100), Codec::Lz4)
+        .unwrap();
+    writer
+        .add_file("/Lotus/Last.bin", &pattern(5000), Codec::Lz4)
+        .unwrap();
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path.clone(), cache_path.clone(), None);
+    reader.read_toc().unwrap();
+    let report = reader.verify().unwrap();
+    assert!(report.is_success(), "{report:?}");
+    assert_eq!(report.checked, 5);
+
+    let blocks = reader.get_file_node("/Lotus/Blocks.bin").unwrap();
+    let last = reader.get_file_node("/Lotus/Last.bin").unwrap();
+
+    // Entries are the `/Lotus` directory followed by the files, in order
+    let entry_offset = |index: u64| TOC_HEADER_LEN + index * TOC_ENTRY_LEN;
+    overwrite(&toc_path, entry_offset(2) + 32, &[0xFF]);
+    overwrite(&toc_path, entry_offset(3) + 28, &42i32.to_le_bytes());
+    let toc_len = std::fs::metadata(&toc_path).unwrap().len();
+    overwrite(&toc_path, toc_len, &[0; 10]);
+
+    // Break the header of the second block and cut the last file short
+    let cache = std::fs::read(&cache_path).unwrap();
+    let first_header = blocks.cache_offset() as usize;
+    let first_comp_len =
+        u32::from_be_bytes(cache[first_header..first_header + 4].try_into().unwrap()) >> 2
+            & 0xFFFFFF;
+    let second_header = first_header as u64 + 8 + first_comp_len as u64;
+    overwrite(&cache_path, second_header + 7, &[0xF0]);
+    OpenOptions::new()
+        .write(true)
+        .open(&cache_path)
+        .unwrap()
+        .set_len(last.cache_offset() as u64 + 10)
+        .unwrap();
+
+    // The TOC cannot be read anymore, but it can still be verified
+    let mut reader = CachePairReader::new(toc_path.clone(), cache_path.clone(), Some(true));
+    assert!(reader.read_toc().is_err());
+    let report = reader.verify().unwrap();
+    assert!(!report.is_success());
+    assert_eq!(report.checked, 5);
+    assert_eq!(report.truncated_len, 10);
+
+    let indices: Vec<_> = report.corrupt.iter().map(|entry| entry.index).collect();
+    assert_eq!(indices, [1, 2, 3, 4]);
+
+    let corrupt = &report.corrupt;
+    assert_eq!(corrupt[0].path, Path::new("/Lotus/Blocks.bin"));
+    assert_eq!(corrupt[0].kind, NodeKind::File);
+    assert!(matches!(
+        corrupt[0].errors[..],
+        [Error::CorruptBlockHeader(_)]
+    ));
+    assert!(matches!(
+        corrupt[1].errors[..],
+        [Error::InvalidEntryName { index: 2, .. }]
+    ));
+    assert_eq!(corrupt[2].path, Path::new("Orphan.bin"));
+    assert!(matches!(
+        corrupt[2].errors[..],
+        [Error::ParentIndexOutOfRange {
+            index: 3,
+            parent_index: 42
+        }]
+    ));
+    assert!(matches!(
+        corrupt[3].errors[..],
+        [Error::EntryOutOfBounds { index: Some(4), .. }]
+    ));
+}
@@ -0,0 +1,185 @@
+//! Tests merging the TOCs of a package collection with [`PackageCollection::vfs`].
+
+use std::fs::File;
+use std::num::NonZeroI64;
+use std::path::Path;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::package::{PackageCollection, PackageType, VfsOptions};
+use lotus_lib::toc::{FileNode, NodeKind};
+use tempfile::TempDir;
+
+/// Writes a cache pair whose files hold their own path and the cache pair file name.
+fn write(dir: &Path, package_type: char, name: &str, timestamp: i64, paths: &[&str]) {
+    let mut writer = CachePairWriter::new(
+        dir.join(format!("{package_type}.{name}.toc")),
+        dir.join(format!("{package_type}.{name}.cache")),
+        Some(true),
+    );
+    writer.set_timestamp(NonZeroI64::new(timestamp).unwrap());
+    for path in paths {
+        let data = format!("{package_type}.{name}:{path}");
+        writer
+            .add_file(*path, data.as_bytes(), Codec::Stored)
+            .unwrap();
+    }
+    writer.write_toc().unwrap();
+}
+
+/// Writes the `Alpha` package with every cache pair type, and the `Beta` package with an H cache
+/// pair whose files are the newest.
+fn write_collection(dir: &Path) {
+    write(
+        dir,
+        'H',
+        "Alpha",
+        10,
+        &["/Lotus/Shared.txt", "/Lotus/H.txt"],
+    );
+    write(
+        dir,
+        'F',
+        "Alpha",
+        30,
+        &["/Lotus/Shared.txt", "/Lotus/F.txt"],
+    );
+    write(
+        dir,
+        'B',
+        "Alpha",
+        20,
+        &["/Lotus/Shared.txt", "/Lotus/B/B.txt"],
+    );
+    write(
+        dir,
+        'H',
+        "Beta",
+        40,
+        &["/Lotus/Shared.txt", "/Lotus/Beta.txt"],
+    );
+}
+
+fn providers(
+    collection: &mut PackageCollection<CachePairReader>,
+    options: VfsOptions,
+) -> Vec<String> {
+    let vfs = collection.vfs_with(options).unwrap();
+    vfs.lookup("/Lotus/Shared.txt")
+        .into_iter()
+        .map(|entry| {
+            format!(
+                "{}.{}",
+                char::from(entry.package_type),
+                entry.package.name()
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn reads_unread_tocs() {
+    let dir = TempDir::new().unwrap();
+    write_collection(dir.path());
+
+    let mut collection = PackageCollection::<CachePairReader>::new(dir.path(), None).unwrap();
+    let vfs = collection.vfs().unwrap();
+    assert!(vfs.exists("/Lotus/Beta.txt"));
+    assert!(vfs.exists("/Lotus/B"));
+    assert!(!vfs.exists("/Lotus/Missing.txt"));
+
+    let entry = vfs.get_file("/Lotus/B/B.txt").unwrap();
+    assert_eq!(entry.decompress_data().unwrap(), b"B.Alpha:/Lotus/B/B.txt");
+
+    assert_eq!(collection.residency().loads, 4);
+    assert_eq!(collection.residency().resident.len(), 4);
+}
+
+#[test]
+fn skips_incomplete_pairs() {
+    let dir = TempDir::new().unwrap();
+    write_collection(dir.path());
+    File::create(dir.path().join("F.Beta.toc")).unwrap();
+    File::create(dir.path().join("H.Gamma.cache")).unwrap();
+
+    let mut collection = PackageCollection::<CachePairReader>::new(dir.path(), None).unwrap();
+    let vfs = collection.vfs().unwrap();
+    assert!(vfs.exists("/Lotus/Beta.txt"));
+    assert_eq!(collection.residency().loads, 4);
+}
+
+#[test]
+fn precedence() {
+    let dir = TempDir::new().unwrap();
+    write_collection(dir.path());
+    let mut collection = PackageCollection::<CachePairReader>::new(dir.path(), None).unwrap();
+
+    // Packages in the collection order, cache pairs in the H, F, B order
+    assert_eq!(
+        providers(&mut collection, VfsOptions::default()),
+        ["H.Alpha", "F.Alpha", "B.Alpha", "H.Beta"]
+    );
+
+    // Listed packages come first
+    let options = VfsOptions {
+        package_order: vec!["Beta".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        providers(&mut collection, options),
+        ["H.Beta", "H.Alpha", "F.Alpha", "B.Alpha"]
+    );
+
+    // Unlisted package types are left out
+    let options = VfsOptions {
+        package_types: vec![PackageType::B, PackageType::H],
+        ..Default::default()
+    };
+    assert_eq!(
+        providers(&mut collection, options),
+        ["B.Alpha", "H.Alpha", "H.Beta"]
+    );
+
+    let vfs = collection.vfs().unwrap();
+    let entry = vfs.get_file("/Lotus/Shared.txt").unwrap();
+    assert_eq!(
+        entry.decompress_data().unwrap(),
+        b"H.Alpha:/Lotus/Shared.txt"
+    );
+
+    // Directories are merged, with the kind of the highest precedence
+    assert_eq!(
+        vfs.read_dir("/Lotus"),
+        [
+            ("B".to_string(), NodeKind::Directory),
+            ("Beta.txt".to_string(), NodeKind::File),
+            ("F.txt".to_string(), NodeKind::File),
+            ("H.txt".to_string(), NodeKind::File),
+            ("Shared.txt".to_string(), NodeKind::File),
+        ]
+    );
+}
+
+#[test]
+fn prefer_newest() {
+    let dir = TempDir::new().unwrap();
+    write_collection(dir.path());
+    let mut collection = PackageCollection::<CachePairReader>::new(dir.path(), None).unwrap();
+
+    let options = VfsOptions {
+        prefer_newest: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        providers(&mut collection, options.clone()),
+        ["H.Beta", "F.Alpha", "B.Alpha", "H.Alpha"]
+    );
+
+    let vfs = collection.vfs_with(options).unwrap();
+    let entry = vfs.get_file("/Lotus/Shared.txt").unwrap();
+    assert_eq!(entry.node.timestamp(), 40);
+    assert_eq!(
+        entry.decompress_data().unwrap(),
+        b"H.Beta:/Lotus/Shared.txt"
+    );
+}
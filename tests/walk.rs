@@ -0,0 +1,178 @@
+//! Tests walking the TOC tree of a cache pair with [`Node::walk`].
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader, CachePairWriter};
+use lotus_lib::compression::Codec;
+use lotus_lib::toc::{Node, Walk, WalkOrder};
+use tempfile::TempDir;
+
+/// Writes a cache pair whose root directory holds `A`, `B.txt` and `C`, in this order.
+fn write_pair(dir: &TempDir) -> CachePairReader {
+    let toc_path = dir.path().join("H.Test.toc");
+    let cache_path = dir.path().join("H.Test.cache");
+
+    let mut writer = CachePairWriter::new(toc_path.clone(), cache_path.clone(), Some(true));
+    for path in ["/A/A1.txt", "/A/Sub/S.txt", "/B.txt", "/C/C1.txt"] {
+        writer.add_file(path, b"data", Codec::Stored).unwrap();
+    }
+    writer.write_toc().unwrap();
+
+    let mut reader = CachePairReader::new(toc_path, cache_path, None);
+    reader.read_toc().unwrap();
+    reader
+}
+
+fn root(reader: &CachePairReader) -> Node {
+    reader.get_directory_node("/").unwrap()
+}
+
+fn names(walk: impl Iterator<Item = (usize, Node)>) -> Vec<(usize, String)> {
+    walk.map(|(depth, node)| (depth, node.name())).collect()
+}
+
+fn expected(nodes: &[(usize, &str)]) -> Vec<(usize, String)> {
+    nodes
+        .iter()
+        .map(|&(depth, name)| (depth, name.to_string()))
+        .collect()
+}
+
+#[test]
+fn depth_first() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+    let root_name = root(&reader).name();
+
+    assert_eq!(
+        names(root(&reader).walk()),
+        expected(&[
+            (0, &root_name),
+            (1, "A"),
+            (2, "A1.txt"),
+            (2, "Sub"),
+            (3, "S.txt"),
+            (1, "B.txt"),
+            (1, "C"),
+            (2, "C1.txt"),
+        ])
+    );
+}
+
+#[test]
+fn breadth_first() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+    let root_name = root(&reader).name();
+
+    assert_eq!(
+        names(root(&reader).walk().order(WalkOrder::BreadthFirst)),
+        expected(&[
+            (0, &root_name),
+            (1, "A"),
+            (1, "B.txt"),
+            (1, "C"),
+            (2, "A1.txt"),
+            (2, "Sub"),
+            (2, "C1.txt"),
+            (3, "S.txt"),
+        ])
+    );
+}
+
+#[test]
+fn subtree() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+
+    // The siblings of the node the walk starts from are not visited
+    let directory = reader.get_directory_node("/A").unwrap();
+    assert_eq!(
+        names(directory.walk()),
+        expected(&[(0, "A"), (1, "A1.txt"), (1, "Sub"), (2, "S.txt")])
+    );
+
+    let file = reader.get_file_node("/B.txt").unwrap();
+    assert_eq!(names(file.walk()), expected(&[(0, "B.txt")]));
+
+    let files: Vec<_> = root(&reader)
+        .walk()
+        .files()
+        .map(|(_, node)| node.path().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(files, ["/A/A1.txt", "/A/Sub/S.txt", "/B.txt", "/C/C1.txt"]);
+}
+
+#[test]
+fn max_depth() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+    let root_name = root(&reader).name();
+
+    assert_eq!(
+        names(root(&reader).walk().max_depth(0)),
+        expected(&[(0, &root_name)])
+    );
+
+    for order in [WalkOrder::DepthFirst, WalkOrder::BreadthFirst] {
+        let walk: Walk<'_> = root(&reader).walk().order(order).max_depth(1);
+        assert_eq!(
+            names(walk),
+            expected(&[(0, &root_name), (1, "A"), (1, "B.txt"), (1, "C")]),
+            "{order:?}"
+        );
+    }
+
+    assert_eq!(
+        names(root(&reader).walk().max_depth(2).files()),
+        expected(&[(2, "A1.txt"), (1, "B.txt"), (2, "C1.txt")])
+    );
+}
+
+#[test]
+fn prune() {
+    let dir = TempDir::new().unwrap();
+    let reader = write_pair(&dir);
+    let root_name = root(&reader).name();
+
+    // Pruned directories are yielded, but not their children
+    let walk = root(&reader).walk().prune(|node| node.name() == "A");
+    assert_eq!(
+        names(walk),
+        expected(&[
+            (0, &root_name),
+            (1, "A"),
+            (1, "B.txt"),
+            (1, "C"),
+            (2, "C1.txt"),
+        ])
+    );
+
+    let walk = root(&reader)
+        .walk()
+        .order(WalkOrder::BreadthFirst)
+        .prune(|node| node.name() == "Sub");
+    assert_eq!(
+        names(walk),
+        expected(&[
+            (0, &root_name),
+            (1, "A"),
+            (1, "B.txt"),
+            (1, "C"),
+            (2, "A1.txt"),
+            (2, "Sub"),
+            (2, "C1.txt"),
+        ])
+    );
+
+    // The predicate is only called on the directories that would be visited
+    let mut visited = Vec::new();
+    let count = root(&reader)
+        .walk()
+        .max_depth(1)
+        .prune(|node| {
+            visited.push(node.name());
+            false
+        })
+        .count();
+    assert_eq!(count, 4);
+    assert_eq!(visited, [root_name]);
+}